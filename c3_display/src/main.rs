@@ -10,12 +10,20 @@ use hal::gpio::*;
 use hal::gpio::{gpioa::*, gpiob::*};
 use hal::prelude::*;
 use hal::rcc::{self, PllConfig};
+use hal::serial::{Event, Rx, Tx};
+use hal::timer::Timer;
 
 use c3_display::brightness::BrightnessAdjustment;
 use c3_display::hub75dma::{Hub75Dma, Hub75DmaWrite};
+use c3_protocol::{
+    answer_delay, heartbeat, version, Board, Commands, Decoder, Identity, Message, Status,
+};
 use cortex_m::peripheral::SYST;
 use cortex_m_semihosting::dbg;
 use embedded_graphics::prelude::*;
+use heapless::consts::*;
+use heapless::spsc::{Consumer, Producer, Queue};
+use rtfm::Mutex;
 
 // We use a global variable to ensure it's only allowated once, since ram
 // is quite tight
@@ -32,11 +40,22 @@ const APP: () = {
         >,
         display_write: Hub75DmaWrite,
         delay: Delay<SYST>,
-        serial: hal::serial::Serial<hal::stm32::USART1>,
+        // Counts `ms`, the SysTick is taken by `delay`
+        ms_timer: Timer<hal::stm32::TIM2>,
+        // Milliseconds since the start
+        #[init(0)]
+        ms: u32,
+        rx: Rx<hal::stm32::USART1>,
+        tx: Tx<hal::stm32::USART1>,
+        // Bytes from the host, received while an image is drawn
+        received: Producer<'static, u8, U64>,
+        to_decoder: Consumer<'static, u8, U64>,
     }
 
     #[init]
     fn init(context: init::Context) -> init::LateResources {
+        static mut RECEIVED: Queue<u8, U64> = Queue(heapless::i::Queue::new());
+
         let p = context.device;
         let pll_cfg = PllConfig::with_hsi(4, 48, 2);
         let rcc_cfg = rcc::Config::pll().pll_cfg(pll_cfg);
@@ -50,7 +69,8 @@ const APP: () = {
 
         use hal::gpio::Speed::VeryHigh;
         let (tx, rx, r1, g1, b1, r2, g2, b2, a, b, c, d, clk, lat, oe) = (
-            // Serial pins
+            // Serial pins, tx is push-pull, so it needs a diode to share the
+            // line back to the host, see the README
             gpioc.pc4.into_floating_input(),
             gpioc.pc5.into_floating_input(),
             // Shift pins
@@ -78,7 +98,13 @@ const APP: () = {
                 .into_floating_input(),
         );
         let serial_config = hal::serial::Config::default().baudrate(9600.bps());
-        let serial = p.USART1.usart(tx, rx, serial_config, &mut rcc).unwrap();
+        let mut serial = p.USART1.usart(tx, rx, serial_config, &mut rcc).unwrap();
+        serial.listen(Event::Rxne);
+        let (tx, rx) = serial.split();
+        let (received, to_decoder) = RECEIVED.split();
+        let mut ms_timer = p.TIM2.timer(&mut rcc);
+        ms_timer.start(1.khz());
+        ms_timer.listen();
         // Get pulse output
         let pwm = p.TIM1.pwm(10.khz(), &mut rcc);
         let oe_pulse = pwm.bind_pin(oe);
@@ -94,13 +120,17 @@ const APP: () = {
             delay,
             display,
             display_write,
-            serial,
+            ms_timer,
+            rx,
+            tx,
+            received,
+            to_decoder,
         }
     }
 
-    #[idle(resources = [delay, display_write, serial])]
+    #[idle(resources = [delay, display_write, ms, tx, to_decoder])]
     #[allow(unused_imports)]
-    fn idle(c: idle::Context) -> ! {
+    fn idle(mut c: idle::Context) -> ! {
         use embedded_graphics::fonts::{Font12x16, Font6x8};
         use embedded_graphics::geometry::Point;
         use embedded_graphics::image::ImageTga;
@@ -155,12 +185,35 @@ const APP: () = {
         // counter += 1;
         // c.resources.display.draw(&imagetmp);
         // c.resources.display.clear();
+        // Messages for other devices are skipped
+        let mut identity = Identity::configured()
+            .unwrap_or_else(|| Identity::new("display").with_configured_groups());
+        identity.join("display").ok();
+        let mut decoder = Decoder::for_device(identity);
+        let commands = Commands::of(&[
+            Message::Identify,
+            Message::Show(0),
+            Message::Clear,
+            Message::Fade,
+        ]);
+        // When the answer to `Identify` is due, see `answer_delay`
+        let mut status_at = None;
+        let (mut round, mut last_status) = (0u16, 0u32);
+        let (mut corrupted, mut failed) = (0u16, 0u16);
         loop {
-            if let Ok(image_num) = c.resources.serial.read() {
-                match image_num {
-                    b'3' => c.resources.display_write.clear(),
+            let now = c.resources.ms.lock(|ms| *ms);
+            let byte = c.resources.to_decoder.dequeue();
+            match byte.and_then(|x| decoder.feed(x)) {
+                Some(Ok(message)) => match message {
+                    Message::Identify => {
+                        // Answer in our slot of this round
+                        let delay = answer_delay(identity.id, round);
+                        status_at = Some(now.wrapping_add(delay as u32));
+                        round = round.wrapping_add(1);
+                    }
+                    Message::Clear => c.resources.display_write.clear(),
 
-                    b'6' => {
+                    Message::Fade => {
                         for i in 0..4 {
                             let mut dimm_disp = BrightnessAdjustment {
                                 display: c.resources.display_write,
@@ -177,12 +230,34 @@ const APP: () = {
                             c.resources.delay.delay_ms(50u8);
                         }
                     }
-                    i => {
-                        (if i < images.len() as u8 {
+                    Message::Show(i) => {
+                        if i < images.len() as u8 {
                             images[i as usize].draw(c.resources.display_write);
-                        })
+                        } else {
+                            failed = failed.saturating_add(1);
+                        }
                     }
-                }
+                    _ => (),
+                },
+                Some(Err(_)) => corrupted = corrupted.saturating_add(1),
+                None => (),
+            }
+            // Also right if `now` wrapped around
+            let due = status_at.map_or(false, |at| now.wrapping_sub(at) as i32 >= 0);
+            if due || now.wrapping_sub(last_status) >= heartbeat(identity.id) {
+                let status = Status {
+                    id: identity.id,
+                    board: Board::Display,
+                    firmware: version(env!("CARGO_PKG_VERSION")),
+                    leds: 64 * 32,
+                    commands,
+                    step_rate: 0,
+                    corrupted,
+                    failed,
+                };
+                Message::Status(status).send(c.resources.tx).ok();
+                status_at = None;
+                last_status = now;
             }
             // image_ewg.draw(c.resources.display_write);
             // c.resources.delay.delay_ms(10000u16);
@@ -203,4 +278,19 @@ const APP: () = {
             .write(|w| unsafe { w.bits(0xFFFFFFFF).cc1if().clear_bit() });
         c.resources.display.output();
     }
+
+    #[task(binds = TIM2, priority = 1, resources = [ms_timer, ms])]
+    fn tick(c: tick::Context) {
+        c.resources.ms_timer.clear_irq();
+        *c.resources.ms = c.resources.ms.wrapping_add(1);
+    }
+
+    // Above the display, it's quick and a missed byte corrupts a message
+    #[task(binds = USART1, priority = 2, resources = [rx, received])]
+    fn receive(c: receive::Context) {
+        if let Ok(byte) = c.resources.rx.read() {
+            // Dropped if idle is that far behind, like a corrupted frame
+            c.resources.received.enqueue(byte).ok();
+        }
+    }
 };
@@ -1,40 +1,343 @@
+use c3_protocol::{Address, Decoder, Message, MAX_FRAME, STATUS_SLOT, STATUS_SLOTS};
 use rand::prelude::*;
-use serialport::open_with_settings;
-use std::path::PathBuf;
+use serialport::{open_with_settings, SerialPort};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use structopt::StructOpt;
 
+mod preview;
+
+/// How long to wait for answers to `Hello` and `Identify`, every slot and a
+/// bit for the transfer
+const ANSWER_TIMEOUT: Duration =
+    Duration::from_millis(STATUS_SLOTS as u64 * STATUS_SLOT as u64 + 200);
+/// How often `status` asks, the answers of a few devices collide every round,
+/// but hardly ever in all of them
+const STATUS_ROUNDS: usize = 3;
+
 /// The host side part of `c3_led_stuff`
 #[derive(StructOpt, Debug)]
 #[structopt(name = "c3_host")]
 struct Opt {
     /// The serial port
     #[structopt(short, long)]
-    tty: PathBuf,
+    tty: Option<PathBuf>,
+    /// Only send to the device with this name, see `C3_DEVICE_ID`, or with
+    /// this id as shown by `status`, e.g. `0x1a2b`
+    #[structopt(long, conflicts_with = "group")]
+    to: Option<String>,
+    /// Only send to the devices in this group, e.g. `tails` or `display`
+    #[structopt(short, long)]
+    group: Option<String>,
+    #[structopt(subcommand)]
+    cmd: Option<Command>,
+}
+
+impl Opt {
+    /// Who the messages are sent to, every device by default
+    fn address(&self) -> Address {
+        match (&self.to, &self.group) {
+            (Some(name), _) => device(name),
+            (None, Some(name)) => Address::group(name),
+            (None, None) => Address::Broadcast,
+        }
+    }
+}
+
+/// The device called `name`, or with the id `name` if it's written in hex
+///
+/// Devices without a name are named after their unique id, which only
+/// they know, so they can only be reached by the id they report.
+fn device(name: &str) -> Address {
+    name.strip_prefix("0x")
+        .and_then(|hex| u16::from_str_radix(hex, 16).ok())
+        .map(Address::Device)
+        .unwrap_or_else(|| Address::device(name))
+}
+
+#[derive(StructOpt, Debug)]
+enum Command {
+    /// Show the strips and the display in the terminal, without any hardware
+    Preview,
+    /// Add a comet with any color to the tails
+    Comet {
+        /// The color in hex, as `rrggbb` or `rrggbbww` with the white die
+        color: Rgbw,
+        /// Length of the trail in mm, the one of the tails if left out
+        #[structopt(short = "l", long)]
+        trail_length: Option<u16>,
+    },
+    /// Switch the tails to one of their built-in effects
+    Effect {
+        /// 0 comets, 1 rainbow, 2 breathing, 3 sparkle, 4 fire, 5 theater chase
+        effect: u8,
+    },
+    /// Replace a color of the palette of the tails, or add one just past its
+    /// end
+    Palette {
+        index: u8,
+        /// The color in hex, as `rrggbb`
+        color: Color,
+    },
+    /// Clear the display
+    Clear,
+    /// Fade ferris in and out on the display
+    Fade,
+    /// Ask the devices what they are and how they're doing, a few times, as
+    /// their answers can collide
+    Status,
+    /// Tune the tails while they're running
+    Set {
+        /// Brightness from 0 to 255
+        #[structopt(short, long)]
+        brightness: Option<u8>,
+        /// How often the comets move and a frame is drawn, in Hz
+        #[structopt(short, long)]
+        step_rate: Option<u8>,
+        /// Trail length of new comets in mm
+        #[structopt(short = "l", long)]
+        trail_length: Option<u16>,
+    },
+}
+
+#[derive(Debug)]
+struct Color([u8; 3]);
+
+impl FromStr for Color {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let s = s.trim_start_matches('#');
+        if s.len() != 6 || !s.is_ascii() {
+            return Err(format!("{} isn't rrggbb", s));
+        }
+        let channel = |i: usize| {
+            u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).map_err(|e| format!("{}: {}", s, e))
+        };
+        Ok(Color([channel(0)?, channel(1)?, channel(2)?]))
+    }
+}
+
+/// A color with the white die of rgbw strips, if it's given
+#[derive(Debug)]
+struct Rgbw {
+    rgb: Color,
+    white: Option<u8>,
+}
+
+impl FromStr for Rgbw {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let s = s.trim_start_matches('#');
+        if s.len() != 8 || !s.is_ascii() {
+            return Ok(Rgbw {
+                rgb: s.parse()?,
+                white: None,
+            });
+        }
+        let white = u8::from_str_radix(&s[6..], 16).map_err(|e| format!("{}: {}", s, e))?;
+        Ok(Rgbw {
+            rgb: s[..6].parse()?,
+            white: Some(white),
+        })
+    }
+}
+
+/// Decides what's shown next and for how long
+struct Show {
+    rng: ThreadRng,
+    prev_image: u8,
+}
+
+impl Show {
+    fn new() -> Self {
+        Self {
+            rng: rand::thread_rng(),
+            // Choose something out of range
+            prev_image: 5,
+        }
+    }
+
+    fn next(&mut self) -> (u8, Duration) {
+        let delay = self.rng.gen_range(10, 20);
+        let mut image;
+        // "Do-while" loop
+        // TODO Abuse while loops less
+        while {
+            image = self.rng.gen_range(0, 5);
+            image == self.prev_image
+        } {}
+        self.prev_image = image;
+        (image, Duration::from_millis(200 * delay))
+    }
 }
 
 fn main() {
     let opt = Opt::from_args();
+    let to = opt.address();
+    match (opt.cmd, opt.tty) {
+        (Some(Command::Preview), _) => preview::run(),
+        (
+            Some(Command::Comet {
+                color,
+                trail_length,
+            }),
+            Some(tty),
+        ) => {
+            let message = Message::Comet {
+                color: color.rgb.0,
+                white: color.white,
+                trail_length_mm: trail_length.unwrap_or(0),
+            };
+            send(&mut *open(&tty), to, message);
+        }
+        (
+            Some(Command::Set {
+                brightness,
+                step_rate,
+                trail_length,
+            }),
+            Some(tty),
+        ) => {
+            let mut serial = open(&tty);
+            let messages = brightness
+                .map(Message::Brightness)
+                .into_iter()
+                .chain(step_rate.map(Message::StepRate))
+                .chain(trail_length.map(Message::TrailLength));
+            for message in messages {
+                send(&mut *serial, to, message);
+            }
+        }
+        (Some(Command::Effect { effect }), Some(tty)) => {
+            send(&mut *open(&tty), to, Message::Effect(effect))
+        }
+        (Some(Command::Palette { index, color }), Some(tty)) => {
+            let message = Message::SetColor {
+                index,
+                color: color.0,
+            };
+            send(&mut *open(&tty), to, message);
+        }
+        (Some(Command::Clear), Some(tty)) => send(&mut *open(&tty), to, Message::Clear),
+        (Some(Command::Fade), Some(tty)) => send(&mut *open(&tty), to, Message::Fade),
+        (Some(Command::Status), Some(tty)) => status(&mut *open(&tty), to),
+        (None, Some(tty)) => run(&tty, to),
+        (Some(_), None) => {
+            eprintln!("This subcommand needs a serial port, see --help");
+            std::process::exit(1);
+        }
+        (None, None) => {
+            eprintln!("Either a serial port or a subcommand is needed, see --help");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn open(tty: &Path) -> Box<dyn SerialPort> {
     let mut settings: serialport::SerialPortSettings = Default::default();
     settings.timeout = Duration::from_millis(10);
     settings.baud_rate = 9600;
-    let mut serial = open_with_settings(&opt.tty, &settings).expect("Open serial port");
-    let mut rng = rand::thread_rng();
-    let mut image = 0;
-    // Choose something out of range
-    let mut prev_image = 5;
+    open_with_settings(tty, &settings).expect("Open serial port")
+}
+
+fn run(tty: &Path, to: Address) {
+    let mut serial = open(tty);
+    if handshake(&mut *serial, to) {
+        println!("Tails are listening");
+    } else {
+        eprintln!("No acknowledgment from the tails, continuing anyway");
+    }
+    let mut show = Show::new();
     loop {
-        let delay = rng.gen_range(10, 20);
-        // "Do-while" loop
-        // TODO Abuse while loops less
-        while {
-            image = rng.gen_range(0, 5);
-            image == prev_image
-        } {}
-        prev_image = image;
-        serial.write(&[image]).expect("Writing to serial port");
+        let (image, delay) = show.next();
+        send(&mut *serial, to, Message::Show(image));
         println!("{}", image);
-        thread::sleep(Duration::from_millis(200 * delay));
+        thread::sleep(delay);
+    }
+}
+
+/// Tell the tails the host is taking over and wait for one of them to answer
+///
+/// The tails answer in their slots, but some may still collide, so this only
+/// tells whether at least one was understood.
+fn handshake(serial: &mut dyn SerialPort, to: Address) -> bool {
+    send(serial, to, Message::Hello);
+    let deadline = Instant::now() + ANSWER_TIMEOUT;
+    let mut decoder = Decoder::new();
+    let mut buf = [0; 16];
+    while Instant::now() < deadline {
+        // Times out after the read timeout of the port
+        if let Ok(n) = serial.read(&mut buf) {
+            for byte in &buf[..n] {
+                if let Some(Ok(Message::Ack)) = decoder.feed(*byte) {
+                    return true;
+                }
+            }
+        }
     }
+    false
+}
+
+/// Print the status of every device at `to` that answers in time
+///
+/// The devices pick another slot for every round, see `answer_delay`, so
+/// asking again gets the answers that collided before. Heartbeats count as
+/// answers too, for groups even those of devices outside of them.
+fn status(serial: &mut dyn SerialPort, to: Address) {
+    let mut decoder = Decoder::new();
+    let mut buf = [0; 64];
+    let mut answered = Vec::new();
+    for _ in 0..STATUS_ROUNDS {
+        send(serial, to, Message::Identify);
+        let deadline = Instant::now() + ANSWER_TIMEOUT;
+        while Instant::now() < deadline {
+            // Times out after the read timeout of the port
+            let n = serial.read(&mut buf).unwrap_or(0);
+            for byte in &buf[..n] {
+                let status = match decoder.feed(*byte) {
+                    Some(Ok(Message::Status(status))) => status,
+                    _ => continue,
+                };
+                let asked = match to {
+                    Address::Device(id) => status.id == id,
+                    _ => true,
+                };
+                if asked && !answered.contains(&status.id) {
+                    answered.push(status.id);
+                    let [major, minor, patch] = status.firmware;
+                    println!(
+                        "0x{:04x}: {:?} {}.{}.{}, {} leds at {} Hz, {} corrupted frames, {} failed messages",
+                        status.id,
+                        status.board,
+                        major,
+                        minor,
+                        patch,
+                        status.leds,
+                        status.step_rate,
+                        status.corrupted,
+                        status.failed
+                    );
+                    let commands: Vec<_> = status.commands.names().collect();
+                    println!("        understands {}", commands.join(", "));
+                }
+            }
+        }
+    }
+    if answered.is_empty() {
+        eprintln!("No device answered");
+        std::process::exit(1);
+    }
+}
+
+/// Send `message` to the devices at `to`
+fn send(serial: &mut dyn SerialPort, to: Address, message: Message) {
+    let mut buf = [0; MAX_FRAME];
+    let len = message.encode_to(to, &mut buf).expect("Encoding message");
+    serial
+        .write_all(&buf[..len])
+        .expect("Writing to serial port");
 }
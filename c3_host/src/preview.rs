@@ -0,0 +1,144 @@
+use std::env;
+use std::io::{self, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use c3_led_tail::consts::*;
+use c3_led_tail::matrix::{Corner, Layout, Matrix, Wiring};
+use c3_led_tail::{Elements, Strip};
+use embedded_graphics::image::ImageTga;
+use embedded_graphics::prelude::*;
+use smart_leds_trait::RGB8;
+
+use crate::Show;
+
+// Time between two frames in ms, like on the tails
+const FRAME_TIME: u16 = 50;
+
+// Width of the terminal, if `COLUMNS` doesn't tell
+const COLUMNS: usize = 80;
+
+// The hub75 panel of c3_display
+const PANEL: Wiring = Wiring {
+    width: 64,
+    height: 32,
+    layout: Layout::Progressive,
+    start: Corner::TopLeft,
+};
+
+/// Runs the show like `run`, but draws the strips and the display to the
+/// terminal
+pub fn run() {
+    // Matching the tail firmwares
+    let mut strips: [Elements<U64>; 2] = [
+        // g071 & microbit
+        Elements::new(
+            Strip {
+                leds_per_metre: 60,
+                length_mm: 6_667,
+            },
+            250,
+        ),
+        // trinket m0
+        Elements::new(
+            Strip {
+                leds_per_metre: 30,
+                length_mm: 2_667,
+            },
+            250,
+        ),
+    ];
+    // Matching c3_display
+    let images = [
+        ImageTga::new(include_bytes!(
+            "../../resources/ferris-flat-happy-small.tga"
+        ))
+        .unwrap(),
+        ImageTga::new(include_bytes!("../../resources/ewg_small.tga")).unwrap(),
+        ImageTga::new(include_bytes!("../../resources/36c3_white_small.tga")).unwrap(),
+        ImageTga::new(include_bytes!("../../resources/rust_2am.tga")).unwrap(),
+        ImageTga::new(include_bytes!("../../resources/c3_led_stuff.tga")).unwrap(),
+    ];
+    let mut panel = [RGB8::default(); 64 * 32];
+
+    let mut show = Show::new();
+    let mut next_image = Instant::now();
+    let columns = env::var("COLUMNS")
+        .ok()
+        .and_then(|x| x.parse().ok())
+        .filter(|x| *x > 0)
+        .unwrap_or(COLUMNS);
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    // Clear the screen once, afterwards everything is just overwritten
+    write!(stdout, "\x1b[2J").unwrap();
+    loop {
+        if Instant::now() >= next_image {
+            let (image, delay) = show.next();
+            next_image += delay;
+            for strip in strips.iter_mut() {
+                if (image as usize) < strip.palette().len() {
+                    strip.add_predefined(image as usize).ok();
+                }
+            }
+            if let Some(tga) = images.get(image as usize) {
+                tga.draw(&mut Matrix {
+                    leds: &mut panel,
+                    wiring: PANEL,
+                });
+            }
+        }
+        for strip in strips.iter_mut() {
+            strip.step(FRAME_TIME);
+        }
+
+        write!(stdout, "\x1b[H").unwrap();
+        for strip in strips.iter_mut() {
+            let leds: Vec<_> = strip.iter().collect();
+            for color in squeeze(&leds, columns) {
+                write!(stdout, "{} ", background(color)).unwrap();
+            }
+            writeln!(stdout, "\x1b[0m").unwrap();
+        }
+        writeln!(stdout).unwrap();
+        // Two rows per character, the upper one as foreground of the half block
+        for y in (0..PANEL.height).step_by(2) {
+            for x in 0..PANEL.width {
+                let upper = panel[PANEL.index(x, y).unwrap()];
+                let lower = panel[PANEL.index(x, y + 1).unwrap()];
+                write!(
+                    stdout,
+                    "\x1b[38;2;{};{};{}m{}\u{2580}",
+                    upper.r,
+                    upper.g,
+                    upper.b,
+                    background(lower)
+                )
+                .unwrap();
+            }
+            writeln!(stdout, "\x1b[0m").unwrap();
+        }
+        stdout.flush().unwrap();
+        thread::sleep(Duration::from_millis(FRAME_TIME as u64));
+    }
+}
+
+/// `leds` in at most `width` characters, each showing the brightest of its
+/// leds, so comets don't vanish between them
+fn squeeze(leds: &[RGB8], width: usize) -> Vec<RGB8> {
+    let cells = leds.len().min(width);
+    (0..cells)
+        .map(|i| {
+            let part = &leds[i * leds.len() / cells..(i + 1) * leds.len() / cells];
+            part.iter().fold(RGB8::default(), |max, x| RGB8 {
+                r: max.r.max(x.r),
+                g: max.g.max(x.g),
+                b: max.b.max(x.b),
+            })
+        })
+        .collect()
+}
+
+fn background(color: RGB8) -> String {
+    format!("\x1b[48;2;{};{};{}m", color.r, color.g, color.b)
+}
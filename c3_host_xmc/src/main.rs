@@ -14,6 +14,8 @@ use hal::time::Bps;
 use hal::usic;
 use nb::block;
 
+use c3_protocol::{Message, MAX_FRAME};
+
 use hal::gpio::{port2::*, *};
 #[rtfm::app(device = xmc1100_hal::xmc1100, peripherals = true)]
 const APP: () = {
@@ -66,7 +68,11 @@ const APP: () = {
                 image == prev_image
             } {}
             prev_image = image;
-            block!(c.resources.serial.write(image)).unwrap();
+            let mut buf = [0; MAX_FRAME];
+            let len = Message::Show(image).encode(&mut buf).unwrap();
+            for byte in &buf[..len] {
+                block!(c.resources.serial.write(*byte)).unwrap();
+            }
             c.resources.delay.delay_ms(200u32 * delay);
         }
     }
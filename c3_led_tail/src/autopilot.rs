@@ -0,0 +1,83 @@
+use core::ops::Range;
+
+use heapless::ArrayLength;
+
+use crate::palette::Palette;
+use crate::{Elements, QueueElement};
+
+/// Adds random comets while the host isn't active
+///
+/// It starts out engaged, stops once the host sends something and takes over
+/// again if the host is silent for longer than `host_timeout`.
+pub struct Autopilot {
+    /// Time between two comets in ms, chosen uniformly from this range
+    pub interval: Range<u32>,
+    /// Colors the comets are chosen from
+    pub palette: Palette,
+    /// Time without anything from the host before taking over again, in ms
+    pub host_timeout: u32,
+    rand: oorandom::Rand32,
+    // Time until the next comet, in ms
+    wait: u32,
+    // Time since the host was last heard from, in ms
+    host_silence: Option<u32>,
+}
+
+impl Autopilot {
+    /// On average a new comet every 750 ms, with the default palette
+    pub fn new(seed: u64) -> Self {
+        Self::with_interval(seed, 500..1000)
+    }
+
+    /// `interval` must not be empty
+    pub fn with_interval(seed: u64, interval: Range<u32>) -> Self {
+        let mut rand = oorandom::Rand32::new(seed);
+        let wait = rand.rand_range(interval.clone());
+        Self {
+            interval,
+            palette: Palette::default(),
+            host_timeout: 10_000,
+            rand,
+            wait,
+            host_silence: None,
+        }
+    }
+
+    /// Call whenever something is received from the host
+    pub fn host_seen(&mut self) {
+        self.host_silence = Some(0);
+    }
+
+    /// Whether the autopilot is adding comets
+    pub fn engaged(&self) -> bool {
+        self.host_silence
+            .map(|silence| silence >= self.host_timeout)
+            .unwrap_or(true)
+    }
+
+    /// Let `dt` ms pass, adding a comet to `elements` if it's time for one
+    pub fn tick<N>(&mut self, dt: u16, elements: &mut Elements<N>)
+    where
+        N: ArrayLength<QueueElement>,
+    {
+        if let Some(silence) = self.host_silence.as_mut() {
+            *silence = silence.saturating_add(dt as u32);
+        }
+        if !self.engaged() {
+            return;
+        }
+        self.wait = self.wait.saturating_sub(dt as u32);
+        if self.wait > 0 {
+            return;
+        }
+        self.wait = self.rand.rand_range(self.interval.clone());
+        if self.palette.is_empty() {
+            return;
+        }
+        let index = self.rand.rand_range(0..self.palette.len() as u32) as usize;
+        if let Ok(color) = self.palette.get(index) {
+            // A full queue is handled by the overflow policy
+            elements.add(color).ok();
+        }
+    }
+}
@@ -0,0 +1,35 @@
+use smart_leds_trait::RGB8;
+
+/// How overlapping trails are combined
+#[derive(Clone, Copy, Default)]
+pub enum Blend {
+    /// Sum of all trails, saturating at full brightness
+    Additive,
+    /// Brightest value of each channel
+    Max,
+    /// Newer trails are drawn over older ones, with their brightness as opacity
+    #[default]
+    AlphaOver,
+}
+
+impl Blend {
+    /// Combine `color` at brightness `level` with the already drawn `below`
+    pub fn apply(&self, below: RGB8, color: RGB8, level: u8) -> RGB8 {
+        RGB8 {
+            r: self.channel(below.r, color.r, level),
+            g: self.channel(below.g, color.g, level),
+            b: self.channel(below.b, color.b, level),
+        }
+    }
+
+    /// Like [`Blend::apply`] for a single channel, e.g. the white die
+    pub fn channel(&self, below: u8, color: u8, level: u8) -> u8 {
+        let scale = |x: u8, level: u16| (x as u16 * level / 256) as u8;
+        let color = scale(color, level as u16);
+        match self {
+            Blend::Additive => below.saturating_add(color),
+            Blend::Max => below.max(color),
+            Blend::AlphaOver => scale(below, 255 - level as u16).saturating_add(color),
+        }
+    }
+}
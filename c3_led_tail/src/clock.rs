@@ -0,0 +1,51 @@
+//! Milliseconds since the start from the SysTick
+//!
+//! The SysTick is optional on the cortex-m0, e.g. the nrf51 of the microbit
+//! doesn't have one.
+
+use cortex_m::peripheral::syst::SystClkSource;
+use cortex_m::peripheral::SYST;
+
+// The SysTick counts down from 24 bits
+const WRAP: u32 = 1 << 24;
+
+/// Counts milliseconds with the free running SysTick
+///
+/// The SysTick wraps every 350 ms at 48 MHz, `now` has to be called more
+/// often than that.
+pub struct Clock {
+    _syst: SYST,
+    cycles_per_ms: u32,
+    // SysTick at the last `now`
+    last: u32,
+    // Cycles not counted as a full ms yet
+    cycles: u32,
+    ms: u32,
+}
+
+impl Clock {
+    /// `core_hz` is the frequency of the core clock
+    pub fn new(mut syst: SYST, core_hz: u32) -> Self {
+        syst.set_clock_source(SystClkSource::Core);
+        syst.set_reload(WRAP - 1);
+        syst.clear_current();
+        syst.enable_counter();
+        Self {
+            _syst: syst,
+            cycles_per_ms: core_hz / 1000,
+            last: SYST::get_current(),
+            cycles: 0,
+            ms: 0,
+        }
+    }
+
+    /// Milliseconds since the clock was started, wrapping after 49 days
+    pub fn now(&mut self) -> u32 {
+        let current = SYST::get_current();
+        self.cycles += self.last.wrapping_sub(current) % WRAP;
+        self.last = current;
+        self.ms = self.ms.wrapping_add(self.cycles / self.cycles_per_ms);
+        self.cycles %= self.cycles_per_ms;
+        self.ms
+    }
+}
@@ -0,0 +1,92 @@
+use smart_leds_trait::{White, RGB8, RGBW};
+
+/// Color of the white die at full brightness, as rgb
+///
+/// Used to figure out how much of a color the white die can take over.
+#[derive(Clone, Copy, Debug)]
+pub struct WhitePoint(pub RGB8);
+
+impl WhitePoint {
+    /// A white die that looks like all rgb dies at full brightness
+    pub const NEUTRAL: WhitePoint = WhitePoint(RGB8 {
+        r: 255,
+        g: 255,
+        b: 255,
+    });
+}
+
+impl Default for WhitePoint {
+    fn default() -> Self {
+        WhitePoint::NEUTRAL
+    }
+}
+
+/// Show as much of `color` as possible with the white die
+pub fn to_rgbw(color: RGB8, white_point: WhitePoint) -> RGBW<u8> {
+    let white = white_point.0;
+    // Brightest the white die can be without adding to any channel
+    let w = [(color.r, white.r), (color.g, white.g), (color.b, white.b)]
+        .iter()
+        .filter(|(_, white)| *white != 0)
+        .map(|(color, white)| (*color as u16 * 255 / *white as u16).min(255))
+        .min()
+        .unwrap_or(0);
+    // What the white die shows of a channel
+    let shown = |white: u8| (white as u16 * w / 255) as u8;
+    RGBW {
+        r: color.r.saturating_sub(shown(white.r)),
+        g: color.g.saturating_sub(shown(white.g)),
+        b: color.b.saturating_sub(shown(white.b)),
+        a: White(w as u8),
+    }
+}
+
+/// Colors that can be shown on rgbw strips
+pub trait IntoRgbw {
+    fn into_rgbw(self, white_point: WhitePoint) -> RGBW<u8>;
+}
+
+impl IntoRgbw for RGB8 {
+    /// See [`to_rgbw`]
+    fn into_rgbw(self, white_point: WhitePoint) -> RGBW<u8> {
+        to_rgbw(self, white_point)
+    }
+}
+
+impl IntoRgbw for RGBW<u8> {
+    /// The white die shows the white of the color, and what it can take over
+    /// of the rest
+    fn into_rgbw(self, white_point: WhitePoint) -> RGBW<u8> {
+        let rgb = RGB8 {
+            r: self.r,
+            g: self.g,
+            b: self.b,
+        };
+        let mut color = to_rgbw(rgb, white_point);
+        color.a = White(color.a.0.saturating_add(self.a.0));
+        color
+    }
+}
+
+/// Converts the colors of an iterator with [`IntoRgbw`]
+pub struct Rgbw<I> {
+    iter: I,
+    white_point: WhitePoint,
+}
+
+impl<I> Rgbw<I> {
+    pub fn new(iter: I, white_point: WhitePoint) -> Self {
+        Self { iter, white_point }
+    }
+}
+
+impl<I> Iterator for Rgbw<I>
+where
+    I: Iterator,
+    I::Item: IntoRgbw,
+{
+    type Item = RGBW<u8>;
+    fn next(&mut self) -> Option<RGBW<u8>> {
+        self.iter.next().map(|x| x.into_rgbw(self.white_point))
+    }
+}
@@ -0,0 +1,277 @@
+//! Everything a tail does with the messages of the host, so the firmwares
+//! only have to move bytes and draw frames
+
+use c3_protocol::{answer_delay, heartbeat, Board, Commands, Decoder, Identity, Message, Status};
+use heapless::ArrayLength;
+use smart_leds_trait::{White, RGB8, RGBW};
+
+use crate::autopilot::Autopilot;
+use crate::{Elements, QueueElement};
+
+/// Time between two frames in ms, until the host sets another step rate
+pub const FRAME_TIME: u16 = 50;
+
+/// What a tail can do and tells the host about itself
+#[derive(Clone, Copy, Debug)]
+pub struct Info {
+    pub board: Board,
+    /// Major, minor and patch version, see `c3_protocol::version`
+    pub firmware: [u8; 3],
+    /// Number of leds in a frame
+    pub leds: u16,
+    /// Number of effects the host can switch between, the comets are the
+    /// first
+    pub effects: usize,
+    /// Faster and the frames can't be written in time
+    pub max_step_rate: u8,
+}
+
+// The messages the tails carry out
+const COMMANDS: [Message; 9] = [
+    Message::Hello,
+    Message::Identify,
+    Message::Show(0),
+    Message::Effect(0),
+    Message::SetColor {
+        index: 0,
+        color: [0; 3],
+    },
+    Message::Comet {
+        color: [0; 3],
+        white: None,
+        trail_length_mm: 0,
+    },
+    Message::Brightness(0),
+    Message::StepRate(0),
+    Message::TrailLength(0),
+];
+
+/// Carries out the messages of the host, lets the autopilot take over while
+/// the host is silent and decides when to draw a frame
+///
+/// All times are in ms, from any clock that counts up, e.g. `clock::Clock`.
+pub struct Controller<N>
+where
+    N: ArrayLength<QueueElement>,
+{
+    elements: Elements<N>,
+    autopilot: Autopilot,
+    info: Info,
+    id: u16,
+    decoder: Decoder,
+    // Index of the effect that's drawn
+    effect: usize,
+    // Scale for `power::Dim`, 256 leaves the colors as they are
+    brightness: u16,
+    frame_time: u16,
+    // When the last frame was due
+    last_frame: u32,
+    // Time passed for the effects
+    time: u32,
+    // When the answers are due that still have to be sent
+    ack_at: Option<u32>,
+    status_at: Option<u32>,
+    // Number of messages answered so far, see `answer_delay`
+    round: u16,
+    // When the last status was sent, asked for or not
+    last_status: u32,
+    corrupted: u16,
+    failed: u16,
+}
+
+impl<N> Controller<N>
+where
+    N: ArrayLength<QueueElement>,
+{
+    /// Messages for other devices than `identity` are skipped
+    pub fn new(
+        elements: Elements<N>,
+        autopilot: Autopilot,
+        identity: Identity,
+        info: Info,
+    ) -> Self {
+        Self {
+            elements,
+            autopilot,
+            info,
+            id: identity.id,
+            decoder: Decoder::for_device(identity),
+            effect: 0,
+            brightness: 256,
+            frame_time: FRAME_TIME,
+            last_frame: 0,
+            time: 0,
+            ack_at: None,
+            status_at: None,
+            round: 0,
+            last_status: 0,
+            corrupted: 0,
+            failed: 0,
+        }
+    }
+
+    /// Add a byte received from the host at `now`
+    pub fn receive(&mut self, byte: u8, now: u32) {
+        match self.decoder.feed(byte) {
+            Some(Ok(message)) => self.apply(message, now),
+            Some(Err(_)) => self.corrupted = self.corrupted.saturating_add(1),
+            None => (),
+        }
+    }
+
+    /// Carry out `message`, counting it as failed if that's not possible
+    pub fn apply(&mut self, message: Message, now: u32) {
+        // Asking for the status doesn't take over
+        if message != Message::Identify {
+            self.autopilot.host_seen();
+        }
+        let done = match message {
+            Message::Hello => {
+                // Let the host know we're listening
+                self.ack_at = Some(self.answer_at(now));
+                true
+            }
+            Message::Identify => {
+                self.status_at = Some(self.answer_at(now));
+                true
+            }
+            // A full queue is handled by the overflow policy
+            Message::Show(n) => self.elements.add_predefined(n as usize).is_ok(),
+            Message::Effect(n) if (n as usize) < self.info.effects => {
+                self.effect = n as usize;
+                true
+            }
+            Message::SetColor {
+                index,
+                color: [r, g, b],
+            } => {
+                let color = RGB8 { r, g, b };
+                self.autopilot.palette.set(index as usize, color).ok();
+                self.elements
+                    .palette_mut()
+                    .set(index as usize, color)
+                    .is_ok()
+            }
+            Message::Comet {
+                color: [r, g, b],
+                white,
+                trail_length_mm,
+            } => {
+                let color = RGBW {
+                    r,
+                    g,
+                    b,
+                    a: White(white.unwrap_or(0)),
+                };
+                let trail_length_mm = Some(trail_length_mm as u32).filter(|x| *x != 0);
+                self.elements.add_rgbw(color, trail_length_mm).is_ok()
+            }
+            Message::Brightness(n) => {
+                self.brightness = n as u16 * 256 / 255;
+                true
+            }
+            Message::StepRate(n) if (1..=self.info.max_step_rate).contains(&n) => {
+                self.frame_time = 1000 / n as u16;
+                true
+            }
+            Message::TrailLength(mm) => {
+                self.elements.set_trail_length(mm as u32);
+                true
+            }
+            // Meant for the host or the display
+            Message::Ack | Message::Status(_) | Message::Clear | Message::Fade => true,
+            // Out of range
+            Message::Effect(_) | Message::StepRate(_) => false,
+        };
+        if !done {
+            self.failed = self.failed.saturating_add(1);
+        }
+    }
+
+    // When to answer a message received at `now`, in the slot of this round
+    fn answer_at(&mut self, now: u32) -> u32 {
+        let delay = answer_delay(self.id, self.round);
+        self.round = self.round.wrapping_add(1);
+        now.wrapping_add(delay as u32)
+    }
+
+    /// What to send to the host at `now`, if anything
+    ///
+    /// Answers are held back until the slot of this device, see
+    /// `answer_delay`. Without being asked, the status is sent every
+    /// `heartbeat`.
+    pub fn reply(&mut self, now: u32) -> Option<Message> {
+        // Also right if `now` wrapped around
+        let due = |at: &mut Option<u32>| match *at {
+            Some(x) if now.wrapping_sub(x) as i32 >= 0 => at.take().is_some(),
+            _ => false,
+        };
+        if due(&mut self.ack_at) {
+            return Some(Message::Ack);
+        }
+        let heartbeat = now.wrapping_sub(self.last_status) >= heartbeat(self.id);
+        if due(&mut self.status_at) || heartbeat {
+            self.last_status = now;
+            Some(Message::Status(self.status()))
+        } else {
+            None
+        }
+    }
+
+    pub fn status(&self) -> Status {
+        Status {
+            id: self.id,
+            board: self.info.board,
+            firmware: self.info.firmware,
+            leds: self.info.leds,
+            commands: Commands::of(&COMMANDS),
+            step_rate: (1000 / self.frame_time) as u8,
+            corrupted: self.corrupted,
+            failed: self.failed,
+        }
+    }
+
+    /// Move everything along if the next frame is due at `now`, which is
+    /// then to be drawn
+    pub fn frame(&mut self, now: u32) -> bool {
+        let frame_time = self.frame_time as u32;
+        let elapsed = now.wrapping_sub(self.last_frame);
+        if elapsed < frame_time {
+            return false;
+        }
+        // A late frame is made up for by the next one, but not any more
+        self.last_frame = if elapsed < 2 * frame_time {
+            self.last_frame.wrapping_add(frame_time)
+        } else {
+            now
+        };
+        self.autopilot.tick(self.frame_time, &mut self.elements);
+        if self.autopilot.engaged() {
+            // The autopilot only adds comets, so show them
+            self.effect = 0;
+        }
+        self.time = self.time.wrapping_add(frame_time);
+        self.elements.step(self.frame_time);
+        true
+    }
+
+    /// Index of the effect to draw
+    pub fn effect(&self) -> usize {
+        self.effect
+    }
+
+    /// Time for `Effect::render`
+    pub fn time(&self) -> u32 {
+        self.time
+    }
+
+    /// Scale for `power::Dim`, 256 leaves the colors as they are
+    pub fn brightness(&self) -> u16 {
+        self.brightness
+    }
+
+    /// The comets, which are the first effect
+    pub fn elements_mut(&mut self) -> &mut Elements<N> {
+        &mut self.elements
+    }
+}
@@ -0,0 +1,284 @@
+use heapless::{ArrayLength, Vec};
+use smart_leds_trait::RGB8;
+
+use crate::{brightness, Elements, QueueElement, COLORS};
+
+/// Number of effects the host can switch between, the comets are the first
+pub const EFFECTS: usize = 6;
+
+/// Something that can be shown on a strip
+pub trait Effect {
+    /// Draw the frame at `time` ms into `leds`
+    fn render(&mut self, time: u32, leds: &mut [RGB8]);
+}
+
+impl<N> Effect for Elements<N>
+where
+    N: ArrayLength<QueueElement>,
+{
+    fn render(&mut self, _time: u32, leds: &mut [RGB8]) {
+        for (led, color) in leds.iter_mut().zip(self.iter()) {
+            *led = color;
+        }
+    }
+}
+
+/// Color wheel, going from red over green and blue back to red
+pub fn hue(hue: u8) -> RGB8 {
+    let hue = hue as u16 * 3;
+    let offset = (hue % 256) as u8;
+    match hue / 256 {
+        0 => RGB8 {
+            r: 255 - offset,
+            g: offset,
+            b: 0,
+        },
+        1 => RGB8 {
+            r: 0,
+            g: 255 - offset,
+            b: offset,
+        },
+        _ => RGB8 {
+            r: offset,
+            g: 0,
+            b: 255 - offset,
+        },
+    }
+}
+
+/// All colors of the rainbow, moving along the strip
+pub struct Rainbow {
+    /// Time for one full cycle through all colors, in ms
+    pub period: u32,
+    /// Number of leds that show a full cycle
+    pub spread: u16,
+}
+
+impl Effect for Rainbow {
+    fn render(&mut self, time: u32, leds: &mut [RGB8]) {
+        // Zero would divide by zero, so treat it like one
+        let (period, spread) = (self.period.max(1), self.spread.max(1) as u32);
+        let offset = (time % period) * 256 / period;
+        for (i, led) in leds.iter_mut().enumerate() {
+            let position = (i as u32 % spread) * 256 / spread;
+            *led = hue((offset + position) as u8);
+        }
+    }
+}
+
+/// The whole strip slowly fading in and out
+pub struct Breathing {
+    pub color: RGB8,
+    /// Time for one breath, in ms
+    pub period: u32,
+}
+
+impl Effect for Breathing {
+    fn render(&mut self, time: u32, leds: &mut [RGB8]) {
+        let period = self.period.max(1);
+        let phase = (time % period) * 512 / period;
+        let level = if phase < 256 { phase } else { 511 - phase };
+        // Squared, so it stays dark for a bit longer
+        let color = brightness(self.color, (level * level / 255) as u16);
+        for led in leds.iter_mut() {
+            *led = color;
+        }
+    }
+}
+
+/// Randomly flashing leds, slowly fading out again
+pub struct Sparkle {
+    pub color: RGB8,
+    /// Chance for each led to light up per frame, in 1/256
+    pub chance: u8,
+    /// Brightness kept per frame, in 1/256
+    pub fade: u8,
+    rand: oorandom::Rand32,
+}
+
+impl Sparkle {
+    pub fn new(color: RGB8, seed: u64) -> Self {
+        Self {
+            color,
+            chance: 2,
+            fade: 220,
+            rand: oorandom::Rand32::new(seed),
+        }
+    }
+}
+
+impl Effect for Sparkle {
+    fn render(&mut self, _time: u32, leds: &mut [RGB8]) {
+        for led in leds.iter_mut() {
+            *led = if self.rand.rand_range(0..256) < self.chance as u32 {
+                self.color
+            } else {
+                brightness(*led, self.fade as u16)
+            }
+        }
+    }
+}
+
+/// Flames rising from the start of the strip
+///
+/// `N` is the maximum number of leds, the rest of the strip stays dark.
+pub struct Fire<N>
+where
+    N: ArrayLength<u8>,
+{
+    /// How fast the flames cool down, higher values give shorter flames
+    pub cooling: u8,
+    /// Chance for a new spark per frame, in 1/256
+    pub sparking: u8,
+    heat: Vec<u8, N>,
+    rand: oorandom::Rand32,
+}
+
+impl<N> Fire<N>
+where
+    N: ArrayLength<u8>,
+{
+    pub fn new(seed: u64) -> Self {
+        Self {
+            cooling: 55,
+            sparking: 120,
+            heat: Vec::new(),
+            rand: oorandom::Rand32::new(seed),
+        }
+    }
+}
+
+impl<N> Effect for Fire<N>
+where
+    N: ArrayLength<u8>,
+{
+    fn render(&mut self, _time: u32, leds: &mut [RGB8]) {
+        let length = leds.len().min(self.heat.capacity());
+        self.heat.resize(length, 0).ok();
+        if length == 0 {
+            return;
+        }
+
+        // Everything cools down a bit
+        let max_cooling = self.cooling as u32 * 10 / length as u32 + 2;
+        for heat in self.heat.iter_mut() {
+            *heat = heat.saturating_sub(self.rand.rand_range(0..max_cooling) as u8);
+        }
+        // Heat rises and diffuses
+        for i in (2..length).rev() {
+            self.heat[i] = ((self.heat[i - 1] as u16 + 2 * self.heat[i - 2] as u16) / 3) as u8;
+        }
+        // New sparks near the bottom
+        if self.rand.rand_range(0..256) < self.sparking as u32 {
+            let i = self.rand.rand_range(0..length.min(7) as u32) as usize;
+            self.heat[i] = self.heat[i].saturating_add(self.rand.rand_range(160..256) as u8);
+        }
+
+        for (led, heat) in leds.iter_mut().zip(self.heat.iter()) {
+            // Black over red and yellow to white
+            let heat = (*heat as u16 * 191 / 255) as u8;
+            let ramp = (heat & 0x3F) << 2;
+            *led = match heat {
+                0x80..=0xFF => RGB8 {
+                    r: 255,
+                    g: 255,
+                    b: ramp,
+                },
+                0x40..=0x7F => RGB8 {
+                    r: 255,
+                    g: ramp,
+                    b: 0,
+                },
+                _ => RGB8 {
+                    r: ramp,
+                    g: 0,
+                    b: 0,
+                },
+            };
+        }
+        for led in leds.iter_mut().skip(length) {
+            *led = RGB8 { r: 0, g: 0, b: 0 };
+        }
+    }
+}
+
+/// Groups of leds, of which every `spacing`th one is lit, marching along
+pub struct TheaterChase {
+    pub color: RGB8,
+    pub spacing: u8,
+    /// Time to move by one led, in ms
+    pub period: u32,
+}
+
+impl Effect for TheaterChase {
+    fn render(&mut self, time: u32, leds: &mut [RGB8]) {
+        let spacing = self.spacing.max(1) as usize;
+        let offset = (time / self.period.max(1)) as usize % spacing;
+        for (i, led) in leds.iter_mut().enumerate() {
+            *led = if i % spacing == offset {
+                self.color
+            } else {
+                RGB8 { r: 0, g: 0, b: 0 }
+            };
+        }
+    }
+}
+
+/// The effects of the tails besides the comets, in the order of
+/// `Message::Effect`
+///
+/// `N` is the maximum number of leds of the fire.
+pub struct Effects<N>
+where
+    N: ArrayLength<u8>,
+{
+    pub rainbow: Rainbow,
+    pub breathing: Breathing,
+    pub sparkle: Sparkle,
+    pub fire: Fire<N>,
+    pub chase: TheaterChase,
+}
+
+impl<N> Effects<N>
+where
+    N: ArrayLength<u8>,
+{
+    /// The effects as every tail shows them, `seed` varies the random ones
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rainbow: Rainbow {
+                period: 5_000,
+                spread: 100,
+            },
+            breathing: Breathing {
+                color: COLORS[3],
+                period: 4_000,
+            },
+            sparkle: Sparkle::new(COLORS[2], seed),
+            fire: Fire::new(seed),
+            chase: TheaterChase {
+                color: COLORS[0],
+                spacing: 3,
+                period: 150,
+            },
+        }
+    }
+
+    /// Draw effect `n` at `time`, the first are the `comets`
+    ///
+    /// `n` has to be less than [`EFFECTS`].
+    pub fn render<M>(&mut self, n: usize, comets: &mut Elements<M>, time: u32, leds: &mut [RGB8])
+    where
+        M: ArrayLength<QueueElement>,
+    {
+        let effects: [&mut dyn Effect; EFFECTS] = [
+            comets,
+            &mut self.rainbow,
+            &mut self.breathing,
+            &mut self.sparkle,
+            &mut self.fire,
+            &mut self.chase,
+        ];
+        effects[n].render(time, leds);
+    }
+}
@@ -0,0 +1,33 @@
+/// How the brightness of a trail decreases from the head to its end
+#[derive(Clone, Copy, Default)]
+pub enum Falloff {
+    #[default]
+    Linear,
+    Quadratic,
+    /// Halves the brightness every 1/8 of the trail
+    Exponential,
+    /// Custom curve, indexed like the `level` of [`Falloff::apply`]
+    Lut(&'static [u8; 256]),
+}
+
+impl Falloff {
+    /// Brightness of a point `level`/255 of the way from the end of the trail
+    /// to the head
+    pub fn apply(&self, level: u8) -> u8 {
+        match self {
+            Falloff::Linear => level,
+            Falloff::Quadratic => (level as u16 * level as u16 / 255) as u8,
+            Falloff::Exponential => {
+                if level == 0 {
+                    return 0;
+                }
+                let distance = 255 - level;
+                // Interpolate between the two nearest powers of two
+                let upper = 255u16 >> (distance / 32);
+                let lower = upper >> 1;
+                (upper - (upper - lower) * (distance % 32) as u16 / 32) as u8
+            }
+            Falloff::Lut(lut) => lut[level as usize],
+        }
+    }
+}
@@ -1,10 +1,54 @@
 #![no_std]
-use core::iter::{Peekable, Rev};
+pub mod autopilot;
+pub mod blend;
+#[cfg(feature = "cortex-m")]
+pub mod clock;
+pub mod color;
+pub mod controller;
+pub mod effects;
+pub mod falloff;
+pub mod matrix;
+pub mod palette;
+pub mod power;
+pub mod seed;
+pub mod segment;
 
+pub use heapless::consts;
 use heapless::consts::*;
-use heapless::spsc::{Iter, Queue};
+use heapless::spsc::Queue;
+use heapless::ArrayLength;
 
-use smart_leds_trait::RGB8;
+use smart_leds_trait::{White, RGB8, RGBW};
+
+use blend::Blend;
+use falloff::Falloff;
+use palette::Palette;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// No space for another comet
+    QueueFull,
+    /// The palette doesn't have a color with that index
+    UnknownColor,
+    /// No space for another color in the palette
+    PaletteFull,
+}
+
+/// What to do when a comet is added while the queue is full
+///
+/// Comets waiting to be spawned count towards the queue as well.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Overflow {
+    /// Don't add the new comet, `add` returns `Error::QueueFull`
+    DropNewest,
+    /// Remove the oldest comet to make room
+    #[default]
+    DropOldest,
+    /// Give the newest comet the colors of the new one instead
+    MergeIntoLast,
+}
+
+/// The default palette
 pub const COLORS: [RGB8; 5] = [
     // Ferris
     RGB8 {
@@ -38,123 +82,430 @@ pub const COLORS: [RGB8; 5] = [
     },
 ];
 
-struct QueueElement {
-    color: RGB8,
-    position: u16,
+/// Fixed point positions have this many steps per led
+const LED: i32 = 256;
+
+/// Physical properties of a led strip
+#[derive(Clone, Copy, Debug)]
+pub struct Strip {
+    pub leds_per_metre: u16,
+    pub length_mm: u32,
+}
+
+impl Strip {
+    /// Number of leds on the strip
+    pub fn leds(&self) -> u16 {
+        self.leds_in(self.length_mm)
+    }
+
+    fn leds_in(&self, mm: u32) -> u16 {
+        (mm * self.leds_per_metre as u32 / 1000) as u16
+    }
+}
+
+/// Speed of a comet in mm/s
+///
+/// Positive values move away from the start of the strip, negative ones
+/// towards it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Velocity(pub i16);
+
+impl Velocity {
+    // 20 leds per second on a 60 leds/m strip
+    pub const FORWARD: Velocity = Velocity(333);
+    pub const BACKWARD: Velocity = Velocity(-333);
+
+    fn direction(self) -> i32 {
+        if self.0 < 0 {
+            -1
+        } else {
+            1
+        }
+    }
+}
+
+impl Default for Velocity {
+    fn default() -> Self {
+        Velocity::FORWARD
+    }
+}
+
+pub struct QueueElement {
+    head: RGB8,
+    tail: RGB8,
+    // For the white die of rgbw strips, mixed into the colors on others
+    white: u8,
+    // Fixed point, in 1/256 leds
+    position: i32,
+    // Movement too small for `position` yet, in 1/1_000_000 of its steps
+    remainder: i32,
+    velocity: Velocity,
+    // In leds
+    trail_length: u16,
+    // Not on the strip yet, waiting for enough space to the previous comet
+    pending: bool,
+}
+
+impl QueueElement {
+    /// Distance of led `pos` behind the head in 1/256 leds, negative if
+    /// it's in front of it
+    fn distance(&self, pos: i32) -> i32 {
+        (self.position - pos * LED) * self.velocity.direction()
+    }
 }
 
-pub struct Elements {
-    queue: Queue<QueueElement, U128, u16, heapless::spsc::SingleCore>,
+/// The comets currently on a strip
+///
+/// `N` is the maximum number of comets that can be visible or waiting to be
+/// spawned at once, e.g. `Elements<U16>` for a short strip or
+/// `Elements<U512>` for a long one.
+pub struct Elements<N = U128>
+where
+    N: ArrayLength<QueueElement>,
+{
+    // Oldest first, the pending comets are always behind the spawned ones
+    queue: Queue<QueueElement, N, u16, heapless::spsc::SingleCore>,
+    // Minimum distance between comets going in the same direction, in 1/256 leds
+    spacing: i32,
+    leds_per_metre: u16,
     length: u16,
     trail_length: u16,
+    falloff: Falloff,
+    blend: Blend,
+    overflow: Overflow,
+    palette: Palette,
 }
 
-impl Elements {
-    pub fn new(length: u16, trail_length: u16) -> Self {
+impl<N> Elements<N>
+where
+    N: ArrayLength<QueueElement>,
+{
+    pub fn new(strip: Strip, trail_length_mm: u32) -> Self {
+        Self::with_falloff(strip, trail_length_mm, Falloff::default())
+    }
+
+    pub fn with_falloff(strip: Strip, trail_length_mm: u32, falloff: Falloff) -> Self {
         let queue = unsafe { Queue::u16_sc() };
 
         Self {
             queue,
-            length,
-            trail_length,
+            spacing: LED,
+            leds_per_metre: strip.leds_per_metre,
+            length: strip.leds(),
+            // At least one led, so there's something to see
+            trail_length: strip.leds_in(trail_length_mm).max(1),
+            falloff,
+            blend: Blend::default(),
+            overflow: Overflow::default(),
+            palette: Palette::default(),
         }
     }
 
-    pub fn step(&mut self) {
-        for x in self.queue.iter_mut() {
-            x.position += 1;
+    /// Number of comets on the strip or waiting to be spawned
+    pub fn len(&self) -> usize {
+        self.queue.len() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Colors available to `add_predefined`
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    pub fn palette_mut(&mut self) -> &mut Palette {
+        &mut self.palette
+    }
+
+    /// Change how overlapping trails are combined
+    pub fn set_blend(&mut self, blend: Blend) {
+        self.blend = blend;
+    }
+
+    /// Change what happens when adding to a full queue
+    pub fn set_overflow(&mut self, overflow: Overflow) {
+        self.overflow = overflow;
+    }
+
+    /// Change the trail length of comets added from now on
+    pub fn set_trail_length(&mut self, trail_length_mm: u32) {
+        self.trail_length = self.leds_in(trail_length_mm).max(1);
+    }
+
+    /// Change the minimum distance between new comets going in the same
+    /// direction, comets added in quicker succession are spawned later
+    pub fn set_spacing(&mut self, spacing_mm: u32) {
+        self.spacing = (spacing_mm as i64 * self.leds_per_metre as i64 * LED as i64 / 1000) as i32;
+    }
+
+    fn leds_in(&self, mm: u32) -> u16 {
+        (mm * self.leds_per_metre as u32 / 1000) as u16
+    }
+
+    /// Move all comets by the distance they travel in `dt` ms
+    pub fn step(&mut self, dt: u16) {
+        for x in self.queue.iter_mut().filter(|x| !x.pending) {
+            // mm/s * ms * leds/m overflows an i32 quickly
+            let moved = x.velocity.0 as i64 * dt as i64 * self.leds_per_metre as i64 * LED as i64
+                + x.remainder as i64;
+            // Keep what's left over, so slow comets still move
+            x.position += (moved / 1_000_000) as i32;
+            x.remainder = (moved % 1_000_000) as i32;
         }
         self.cull();
+        self.spawn();
     }
 
-    pub fn add(&mut self, color: RGB8) -> Result<(), ()> {
-        let element = QueueElement { color, position: 0 };
-        if self
-            .queue
-            .iter_mut()
-            .next_back()
-            .map(|x| x.position != 0)
-            .unwrap_or(true)
-        {
-            self.queue.enqueue(element).map_err(|_| ())
-        } else {
-            // Too many elements, skip this one
-            Ok(())
-        }
+    pub fn add(&mut self, color: RGB8) -> Result<(), Error> {
+        self.add_with_velocity(color, Velocity::default())
     }
 
-    pub fn add_predefined(&mut self, num: usize) -> Result<(), ()> {
-        let element = QueueElement {
-            color: COLORS[num],
-            position: 0,
+    /// Add a comet with a trail of its own length instead of the one of the
+    /// strip
+    pub fn add_with_trail(&mut self, color: RGB8, trail_length_mm: u32) -> Result<(), Error> {
+        let trail_length = self.leds_in(trail_length_mm).max(1);
+        self.add_element(color, color, 0, Velocity::default(), trail_length)
+    }
+
+    /// Add a comet that also lights the white die of rgbw strips, see
+    /// `iter_rgbw`, with a trail of its own length if there's one
+    pub fn add_rgbw(&mut self, color: RGBW<u8>, trail_length_mm: Option<u32>) -> Result<(), Error> {
+        let trail_length = trail_length_mm.map_or(self.trail_length, |x| self.leds_in(x).max(1));
+        let rgb = RGB8 {
+            r: color.r,
+            g: color.g,
+            b: color.b,
         };
-        if self
-            .queue
-            .iter_mut()
-            .next_back()
-            .map(|x| x.position != 0)
-            .unwrap_or(true)
-        {
-            self.queue.enqueue(element).map_err(|_| ())
+        self.add_element(rgb, rgb, color.a.0, Velocity::default(), trail_length)
+    }
+
+    /// Add a comet that starts at the end of the strip it's moving away from
+    ///
+    /// If there isn't enough space to the last comet, it waits for it to move
+    /// away first.
+    pub fn add_with_velocity(&mut self, color: RGB8, velocity: Velocity) -> Result<(), Error> {
+        self.add_gradient(color, color, velocity)
+    }
+
+    /// Add a comet which fades from the `head` color into the `tail` color
+    /// along its trail
+    pub fn add_gradient(
+        &mut self,
+        head: RGB8,
+        tail: RGB8,
+        velocity: Velocity,
+    ) -> Result<(), Error> {
+        self.add_element(head, tail, 0, velocity, self.trail_length)
+    }
+
+    fn add_element(
+        &mut self,
+        head: RGB8,
+        tail: RGB8,
+        white: u8,
+        velocity: Velocity,
+        trail_length: u16,
+    ) -> Result<(), Error> {
+        let position = if velocity.0 < 0 {
+            (self.length as i32 - 1) * LED
         } else {
-            // Too many elements, skip this one
-            Ok(())
+            0
+        };
+        let element = QueueElement {
+            head,
+            tail,
+            white,
+            position,
+            remainder: 0,
+            velocity,
+            trail_length,
+            pending: true,
+        };
+        push(&mut self.queue, element, self.overflow)?;
+        self.spawn();
+        Ok(())
+    }
+
+    // Move pending comets onto the strip in the order they were added, once
+    // they're far enough from the previous ones
+    fn spawn(&mut self) {
+        while let Some(x) = self.queue.iter().find(|x| x.pending) {
+            let (position, direction) = (x.position, x.velocity.direction());
+            let blocked = self.queue.iter().any(|y| {
+                !y.pending
+                    && y.velocity.direction() == direction
+                    && (y.position - position) * direction < self.spacing
+            });
+            if blocked {
+                break;
+            }
+            if let Some(x) = self.queue.iter_mut().find(|x| x.pending) {
+                x.pending = false;
+            }
         }
     }
 
+    pub fn add_predefined(&mut self, num: usize) -> Result<(), Error> {
+        self.add(self.palette.get(num)?)
+    }
+
     // Drop elements that aren't visible anymore
     pub fn cull(&mut self) {
-        while self
-            .queue
-            .peek()
-            .map(|x| x.position > (self.length + self.trail_length))
-            .unwrap_or(false)
-        {
-            self.queue.dequeue();
+        // Elements may leave in any order, so go through the whole queue once
+        for _ in 0..self.queue.len() {
+            if let Some(x) = self.queue.dequeue() {
+                let tail = x.position - x.velocity.direction() * x.trail_length as i32 * LED;
+                let (start, end) = if tail < x.position {
+                    (tail, x.position)
+                } else {
+                    (x.position, tail)
+                };
+                // The head may still light up the first led, see `ElementIter`
+                if x.pending || (end > -LED && start < self.length as i32 * LED) {
+                    // Can't fail, since we just made room for it
+                    self.queue.enqueue(x).ok();
+                }
+            }
         }
     }
 
-    pub fn iter<'a>(&'a mut self) -> ElementIter<'a> {
+    /// Colors of the leds, the white of comets is mixed in
+    pub fn iter<'a>(&'a mut self) -> ElementIter<'a, N> {
         ElementIter {
-            iter: self.queue.iter().rev().peekable(),
+            queue: &self.queue,
             pos: 0,
-            step: 255 / self.trail_length,
-            trail_length: self.trail_length,
             length: self.length,
+            falloff: self.falloff,
+            blend: self.blend,
         }
     }
+
+    /// Colors of the leds for rgbw strips, the white of comets is left to
+    /// the white die, see `color::Rgbw`
+    pub fn iter_rgbw<'a>(&'a mut self) -> ElementRgbwIter<'a, N> {
+        ElementRgbwIter(self.iter())
+    }
+}
+
+/// Add `element` to `queue`, making room according to `overflow`
+fn push<N>(
+    queue: &mut Queue<QueueElement, N, u16, heapless::spsc::SingleCore>,
+    element: QueueElement,
+    overflow: Overflow,
+) -> Result<(), Error>
+where
+    N: ArrayLength<QueueElement>,
+{
+    match queue.enqueue(element) {
+        Ok(()) => Ok(()),
+        Err(element) => match overflow {
+            Overflow::DropNewest => Err(Error::QueueFull),
+            Overflow::DropOldest => {
+                queue.dequeue();
+                // Can't fail, since we just made room for it
+                queue.enqueue(element).ok();
+                Ok(())
+            }
+            Overflow::MergeIntoLast => {
+                if let Some(last) = queue.iter_mut().last() {
+                    last.head = element.head;
+                    last.tail = element.tail;
+                    last.white = element.white;
+                }
+                Ok(())
+            }
+        },
+    }
 }
 
-pub struct ElementIter<'a> {
-    iter: Peekable<Rev<Iter<'a, QueueElement, U128, u16, heapless::spsc::SingleCore>>>,
+pub struct ElementIter<'a, N>
+where
+    N: ArrayLength<QueueElement>,
+{
+    queue: &'a Queue<QueueElement, N, u16, heapless::spsc::SingleCore>,
     pos: u16,
-    trail_length: u16,
-    step: u16,
     length: u16,
+    falloff: Falloff,
+    blend: Blend,
 }
 
-impl<'a> Iterator for ElementIter<'a> {
-    type Item = RGB8;
-    fn next(&mut self) -> Option<RGB8> {
+impl<'a, N> ElementIter<'a, N>
+where
+    N: ArrayLength<QueueElement>,
+{
+    /// Color and white of the next led
+    fn next_rgbw(&mut self) -> Option<(RGB8, u8)> {
         let pos = self.pos;
         self.pos += 1;
         // Check if we exceeded the length
         if pos >= self.length {
             return None;
         }
-        // Check if it's time for the next element
-        // We don't return now to get results for the full length of the chain
-        if self.iter.peek().map(|x| x.position < pos).unwrap_or(false) {
-            self.iter.next();
-        }
-        if let Some(x) = self.iter.peek() {
-            let distance = x.position - pos;
-            let multiplier = self.trail_length.saturating_sub(distance) as u16 * self.step as u16;
-            Some(brightness(x.color, multiplier))
-        } else {
-            // Return dark pixel
-            Some(RGB8 { r: 0, g: 0, b: 0 })
+        // Oldest first, so newer trails end up on top
+        let mut color = RGB8 { r: 0, g: 0, b: 0 };
+        let mut white = 0;
+        for x in self.queue.iter().filter(|x| !x.pending) {
+            let trail_length = x.trail_length as i32 * LED;
+            let distance = x.distance(pos as i32);
+            let (level, gradient) = if distance > -LED && distance < 0 {
+                // Anti-aliasing: The head is partially on this led
+                (self.falloff.apply(255) as i32 * (LED + distance) / LED, 0)
+            } else if distance >= 0 && distance <= trail_length {
+                let gradient = distance * 255 / trail_length;
+                (self.falloff.apply(255 - gradient as u8) as i32, gradient)
+            } else {
+                continue;
+            };
+            let x_color = mix(x.head, x.tail, gradient as u8);
+            color = self.blend.apply(color, x_color, level as u8);
+            white = self.blend.channel(white, x.white, level as u8);
         }
+        Some((color, white))
+    }
+}
+
+impl<'a, N> Iterator for ElementIter<'a, N>
+where
+    N: ArrayLength<QueueElement>,
+{
+    type Item = RGB8;
+    fn next(&mut self) -> Option<RGB8> {
+        self.next_rgbw().map(|(color, white)| RGB8 {
+            r: color.r.saturating_add(white),
+            g: color.g.saturating_add(white),
+            b: color.b.saturating_add(white),
+        })
+    }
+}
+
+pub struct ElementRgbwIter<'a, N>(ElementIter<'a, N>)
+where
+    N: ArrayLength<QueueElement>;
+
+impl<'a, N> Iterator for ElementRgbwIter<'a, N>
+where
+    N: ArrayLength<QueueElement>,
+{
+    type Item = RGBW<u8>;
+    fn next(&mut self) -> Option<RGBW<u8>> {
+        self.0.next_rgbw().map(|(color, white)| RGBW {
+            r: color.r,
+            g: color.g,
+            b: color.b,
+            a: White(white),
+        })
+    }
+}
+
+/// Linear interpolation from `a` (at 0) to `b` (at 255)
+fn mix(a: RGB8, b: RGB8, amount: u8) -> RGB8 {
+    let mix = |a: u8, b: u8| (a as i32 + (b as i32 - a as i32) * amount as i32 / 255) as u8;
+    RGB8 {
+        r: mix(a.r, b.r),
+        g: mix(a.g, b.g),
+        b: mix(a.b, b.b),
     }
 }
 
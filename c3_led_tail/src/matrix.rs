@@ -0,0 +1,86 @@
+#[cfg(feature = "embedded-graphics")]
+use embedded_graphics::{
+    drawable::Pixel, geometry::Size, pixelcolor::Rgb888, pixelcolor::RgbColor, DrawTarget,
+};
+#[cfg(feature = "embedded-graphics")]
+use smart_leds_trait::RGB8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Layout {
+    /// Every row starts on the same side
+    Progressive,
+    /// Every other row runs backwards, as the strip zigzags through the matrix
+    Serpentine,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// How a strip is wound into a matrix, row by row
+#[derive(Clone, Copy, Debug)]
+pub struct Wiring {
+    pub width: u32,
+    pub height: u32,
+    pub layout: Layout,
+    /// Corner of the first led
+    pub start: Corner,
+}
+
+impl Wiring {
+    /// Index of the led at `x`, `y` in the strip
+    pub fn index(&self, x: u32, y: u32) -> Option<usize> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let x = match self.start {
+            Corner::TopRight | Corner::BottomRight => self.width - 1 - x,
+            Corner::TopLeft | Corner::BottomLeft => x,
+        };
+        let y = match self.start {
+            Corner::BottomLeft | Corner::BottomRight => self.height - 1 - y,
+            Corner::TopLeft | Corner::TopRight => y,
+        };
+        let x = if self.layout == Layout::Serpentine && y % 2 == 1 {
+            self.width - 1 - x
+        } else {
+            x
+        };
+        Some((y * self.width + x) as usize)
+    }
+}
+
+/// Strip wound into a matrix, to draw on it with embedded-graphics
+#[cfg(feature = "embedded-graphics")]
+pub struct Matrix<'a> {
+    pub leds: &'a mut [RGB8],
+    pub wiring: Wiring,
+}
+
+#[cfg(feature = "embedded-graphics")]
+impl<'a> DrawTarget<Rgb888> for Matrix<'a> {
+    fn draw_pixel(&mut self, item: Pixel<Rgb888>) {
+        let Pixel(coord, color) = item;
+        if coord.x < 0 || coord.y < 0 {
+            return;
+        }
+        if let Some(led) = self
+            .wiring
+            .index(coord.x as u32, coord.y as u32)
+            .and_then(|i| self.leds.get_mut(i))
+        {
+            *led = RGB8 {
+                r: color.r(),
+                g: color.g(),
+                b: color.b(),
+            };
+        }
+    }
+    fn size(&self) -> Size {
+        Size::new(self.wiring.width, self.wiring.height)
+    }
+}
@@ -0,0 +1,69 @@
+use heapless::consts::*;
+use heapless::{ArrayLength, Vec};
+use smart_leds_trait::RGB8;
+
+use crate::{Error, COLORS};
+
+/// Colors that can be referred to by their index, e.g. by the host
+///
+/// Holds up to `N` colors and starts out with the 36c3 [`COLORS`].
+pub struct Palette<N = U16>
+where
+    N: ArrayLength<RGB8>,
+{
+    colors: Vec<RGB8, N>,
+}
+
+impl<N> Palette<N>
+where
+    N: ArrayLength<RGB8>,
+{
+    /// A palette without any colors
+    pub fn empty() -> Self {
+        Self { colors: Vec::new() }
+    }
+
+    pub fn get(&self, index: usize) -> Result<RGB8, Error> {
+        self.colors.get(index).cloned().ok_or(Error::UnknownColor)
+    }
+
+    /// Replace the color at `index`, or add it if `index` is just past the end
+    pub fn set(&mut self, index: usize, color: RGB8) -> Result<(), Error> {
+        if index == self.colors.len() {
+            self.colors.push(color).map_err(|_| Error::PaletteFull)
+        } else {
+            let entry = self.colors.get_mut(index).ok_or(Error::UnknownColor)?;
+            *entry = color;
+            Ok(())
+        }
+    }
+
+    /// Remove all colors from `index` on
+    pub fn truncate(&mut self, index: usize) {
+        self.colors.truncate(index);
+    }
+
+    pub fn len(&self) -> usize {
+        self.colors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.colors.is_empty()
+    }
+}
+
+impl<N> Default for Palette<N>
+where
+    N: ArrayLength<RGB8>,
+{
+    fn default() -> Self {
+        let mut palette = Self::empty();
+        for (i, color) in COLORS.iter().enumerate() {
+            // Palettes smaller than `COLORS` only get the first few
+            if palette.set(i, *color).is_err() {
+                break;
+            }
+        }
+        palette
+    }
+}
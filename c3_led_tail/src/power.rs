@@ -0,0 +1,138 @@
+use smart_leds_trait::{White, RGB8, RGBW};
+
+/// Current drawn by one led
+#[derive(Clone, Copy, Debug)]
+pub struct ChipModel {
+    /// Current of one color die at full brightness, in µA
+    pub channel_ua: u32,
+    /// Current while all dies are off, in µA
+    pub idle_ua: u32,
+}
+
+impl ChipModel {
+    pub const WS2812: ChipModel = ChipModel {
+        channel_ua: 20_000,
+        idle_ua: 1_000,
+    };
+    pub const SK6812W: ChipModel = ChipModel {
+        channel_ua: 20_000,
+        idle_ua: 1_000,
+    };
+    pub const APA102: ChipModel = ChipModel {
+        channel_ua: 20_000,
+        idle_ua: 700,
+    };
+}
+
+/// Colors whose current can be estimated and limited
+pub trait Channels: Copy {
+    /// Sum of all channels
+    fn total(&self) -> u32;
+    /// Scale all channels by `scale`/256
+    fn dim(&self, scale: u16) -> Self;
+}
+
+impl Channels for RGB8 {
+    fn total(&self) -> u32 {
+        self.r as u32 + self.g as u32 + self.b as u32
+    }
+
+    fn dim(&self, scale: u16) -> Self {
+        RGB8 {
+            r: (self.r as u16 * scale / 256) as u8,
+            g: (self.g as u16 * scale / 256) as u8,
+            b: (self.b as u16 * scale / 256) as u8,
+        }
+    }
+}
+
+impl Channels for RGBW<u8> {
+    fn total(&self) -> u32 {
+        self.r as u32 + self.g as u32 + self.b as u32 + self.a.0 as u32
+    }
+
+    fn dim(&self, scale: u16) -> Self {
+        RGBW {
+            r: (self.r as u16 * scale / 256) as u8,
+            g: (self.g as u16 * scale / 256) as u8,
+            b: (self.b as u16 * scale / 256) as u8,
+            a: White((self.a.0 as u16 * scale / 256) as u8),
+        }
+    }
+}
+
+/// Keeps the current of a frame below what the supply can deliver
+///
+/// Works on the colors as they're sent to the leds, i.e. after gamma
+/// correction.
+#[derive(Clone, Copy, Debug)]
+pub struct PowerLimit {
+    pub model: ChipModel,
+    pub budget_ma: u32,
+}
+
+impl PowerLimit {
+    /// Estimated current of a frame, in mA
+    pub fn current_ma<C, I>(&self, frame: I) -> u32
+    where
+        C: Channels,
+        I: Iterator<Item = C>,
+    {
+        let (idle, active) = self.current_ua(frame);
+        ((idle + active) / 1000) as u32
+    }
+
+    /// Factor in 1/256 all colors of the frame have to be scaled by to stay
+    /// within the budget
+    pub fn scale<C, I>(&self, frame: I) -> u16
+    where
+        C: Channels,
+        I: Iterator<Item = C>,
+    {
+        let (idle, active) = self.current_ua(frame);
+        let budget = self.budget_ma as u64 * 1000;
+        if idle + active <= budget || active == 0 {
+            256
+        } else {
+            // The idle current can't be reduced
+            (budget.saturating_sub(idle) * 256 / active) as u16
+        }
+    }
+
+    /// Idle and active current in µA
+    fn current_ua<C, I>(&self, frame: I) -> (u64, u64)
+    where
+        C: Channels,
+        I: Iterator<Item = C>,
+    {
+        frame.fold((0, 0), |(idle, active), color| {
+            (
+                idle + self.model.idle_ua as u64,
+                active + self.model.channel_ua as u64 * color.total() as u64 / 255,
+            )
+        })
+    }
+}
+
+/// Scales all colors of an iterator, see [`PowerLimit::scale`]
+pub struct Dim<I> {
+    iter: I,
+    scale: u16,
+}
+
+impl<I> Dim<I> {
+    pub fn new(iter: I, scale: u16) -> Self {
+        Self { iter, scale }
+    }
+}
+
+impl<C, I> Iterator for Dim<I>
+where
+    C: Channels,
+    I: Iterator<Item = C>,
+{
+    type Item = C;
+    fn next(&mut self) -> Option<C> {
+        self.iter.next().map(|x| x.dim(self.scale))
+    }
+}
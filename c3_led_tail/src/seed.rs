@@ -0,0 +1,16 @@
+//! Seeds for the random number generators, so that devices running on their
+//! own don't all show the same sequence
+
+/// Seed derived from `id`, e.g. the unique id of the mcu
+pub fn from_id(id: &[u8]) -> u64 {
+    // FNV-1a
+    id.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Seed derived from the `C3_DEVICE_ID` environment variable at build time,
+/// if it's set
+pub fn configured() -> Option<u64> {
+    option_env!("C3_DEVICE_ID").map(|id| from_id(id.as_bytes()))
+}
@@ -0,0 +1,57 @@
+use smart_leds_trait::RGB8;
+
+/// Part of the logical line of leds, shown on one physical run of leds
+///
+/// A path spanning several strips is rendered into one frame, with each
+/// strip showing its own segment of it. Leds of the frame that aren't part of
+/// any segment act as gaps, e.g. for the distance between two strips.
+#[derive(Clone, Copy, Debug)]
+pub struct Segment {
+    /// Index of the first led of the frame shown on this run
+    pub start: usize,
+    /// Number of leds on this run
+    pub length: usize,
+    /// The run is wired from the end of the segment to its start
+    pub reversed: bool,
+}
+
+impl Segment {
+    /// Colors for the leds of this run, in the order they're wired
+    ///
+    /// Leds past the end of `frame` stay dark.
+    pub fn map<'a>(&self, frame: &'a [RGB8]) -> SegmentIter<'a> {
+        SegmentIter {
+            frame,
+            segment: *self,
+            pos: 0,
+        }
+    }
+}
+
+pub struct SegmentIter<'a> {
+    frame: &'a [RGB8],
+    segment: Segment,
+    pos: usize,
+}
+
+impl<'a> Iterator for SegmentIter<'a> {
+    type Item = RGB8;
+    fn next(&mut self) -> Option<RGB8> {
+        let pos = self.pos;
+        if pos >= self.segment.length {
+            return None;
+        }
+        self.pos += 1;
+        let offset = if self.segment.reversed {
+            self.segment.length - 1 - pos
+        } else {
+            pos
+        };
+        Some(
+            self.frame
+                .get(self.segment.start + offset)
+                .cloned()
+                .unwrap_or(RGB8 { r: 0, g: 0, b: 0 }),
+        )
+    }
+}
@@ -0,0 +1,50 @@
+mod common;
+
+use c3_led_tail::consts::*;
+use c3_led_tail::{Elements, Velocity};
+use common::{elements, FRAME_TIME};
+use smart_leds_trait::RGB8;
+
+const WHITE: RGB8 = RGB8 {
+    r: 255,
+    g: 255,
+    b: 255,
+};
+
+/// Frame after a comet moved with `velocity` for `frames`
+fn frame_after(velocity: Velocity, frames: usize) -> Vec<RGB8> {
+    let mut elements: Elements<U16> = elements();
+    elements.add_with_velocity(WHITE, velocity).unwrap();
+    for _ in 0..frames {
+        elements.step(FRAME_TIME);
+    }
+    elements.iter().collect()
+}
+
+#[test]
+fn head_on_led() {
+    // 0.75 leds per frame, so at led 3
+    let frame = frame_after(Velocity(250), 4);
+    assert_eq!(frame[3].r, 254);
+    assert_eq!(frame[4], RGB8::default());
+}
+
+#[test]
+fn head_between_leds() {
+    // At led 1.5, so led 2 is half on
+    let frame = frame_after(Velocity(250), 2);
+    assert!(frame[1].r > 240);
+    assert!((120..=135).contains(&frame[2].r));
+    assert_eq!(frame[3], RGB8::default());
+}
+
+#[test]
+fn head_brightens_next_led_gradually() {
+    // About 0.25 leds per frame, so at led 2.25, 2.5 and 2.75
+    let levels: Vec<_> = [9, 10, 11]
+        .iter()
+        .map(|frames| frame_after(Velocity(83), *frames)[3].r)
+        .collect();
+    assert!(levels[0] > 0);
+    assert!(levels[0] < levels[1] && levels[1] < levels[2]);
+}
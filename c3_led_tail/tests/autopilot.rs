@@ -0,0 +1,122 @@
+mod common;
+
+use c3_led_tail::autopilot::Autopilot;
+use c3_led_tail::consts::*;
+use c3_led_tail::palette::Palette;
+use c3_led_tail::Elements;
+use common::{elements, FRAME_TIME};
+use smart_leds_trait::RGB8;
+
+fn run(autopilot: &mut Autopilot, elements: &mut Elements<U16>, frames: usize) -> Vec<Vec<RGB8>> {
+    (0..frames)
+        .map(|_| {
+            autopilot.tick(FRAME_TIME, elements);
+            elements.step(FRAME_TIME);
+            elements.iter().collect()
+        })
+        .collect()
+}
+
+#[test]
+fn waits_for_interval() {
+    let mut autopilot = Autopilot::with_interval(0, 1_000..1_001);
+    let mut elements: Elements<U16> = elements();
+    for _ in 0..19 {
+        autopilot.tick(FRAME_TIME, &mut elements);
+    }
+    assert!(elements.is_empty());
+    autopilot.tick(FRAME_TIME, &mut elements);
+    assert_eq!(elements.len(), 1);
+    for _ in 0..19 {
+        autopilot.tick(FRAME_TIME, &mut elements);
+    }
+    assert_eq!(elements.len(), 1);
+    autopilot.tick(FRAME_TIME, &mut elements);
+    assert_eq!(elements.len(), 2);
+}
+
+#[test]
+fn interval_within_range() {
+    let mut autopilot = Autopilot::with_interval(42, 500..1_000);
+    let mut elements: Elements<U16> = elements();
+    let mut last = 0;
+    for frame in 1..=200 {
+        let before = elements.len();
+        autopilot.tick(FRAME_TIME, &mut elements);
+        if elements.len() > before {
+            if last != 0 {
+                let interval = (frame - last) * FRAME_TIME as usize;
+                assert!(interval >= 500 && interval < 1_000 + FRAME_TIME as usize);
+            }
+            last = frame;
+        }
+        elements.step(FRAME_TIME);
+    }
+    assert_ne!(last, 0);
+}
+
+#[test]
+fn same_seed_same_show() {
+    let frames = run(&mut Autopilot::new(7), &mut elements(), 200);
+    assert_eq!(frames, run(&mut Autopilot::new(7), &mut elements(), 200));
+    assert_ne!(frames, run(&mut Autopilot::new(8), &mut elements(), 200));
+}
+
+#[test]
+fn uses_own_palette() {
+    let red = RGB8 { r: 255, g: 0, b: 0 };
+    let mut autopilot = Autopilot::with_interval(0, 100..200);
+    autopilot.palette = Palette::empty();
+    autopilot.palette.set(0, red).unwrap();
+    let frames = run(&mut autopilot, &mut elements(), 100);
+    let lit: Vec<_> = frames
+        .iter()
+        .flatten()
+        .filter(|x| **x != RGB8::default())
+        .collect();
+    assert!(!lit.is_empty());
+    assert!(lit.iter().all(|x| x.g == 0 && x.b == 0));
+}
+
+#[test]
+fn empty_palette_adds_nothing() {
+    let mut autopilot = Autopilot::with_interval(0, 100..200);
+    autopilot.palette = Palette::empty();
+    let mut elements: Elements<U16> = elements();
+    run(&mut autopilot, &mut elements, 100);
+    assert!(elements.is_empty());
+}
+
+#[test]
+fn quiet_while_host_active() {
+    let mut autopilot = Autopilot::with_interval(0, 100..200);
+    autopilot.host_timeout = 1_000;
+    let mut elements: Elements<U16> = elements();
+    autopilot.host_seen();
+    for _ in 0..19 {
+        autopilot.tick(FRAME_TIME, &mut elements);
+    }
+    assert!(!autopilot.engaged());
+    assert!(elements.is_empty());
+    autopilot.host_seen();
+    for _ in 0..19 {
+        autopilot.tick(FRAME_TIME, &mut elements);
+    }
+    assert!(!autopilot.engaged());
+    assert!(elements.is_empty());
+}
+
+#[test]
+fn takes_over_after_host_timeout() {
+    let mut autopilot = Autopilot::with_interval(0, 100..200);
+    autopilot.host_timeout = 1_000;
+    let mut elements: Elements<U16> = elements();
+    assert!(autopilot.engaged());
+    autopilot.host_seen();
+    for _ in 0..20 {
+        autopilot.tick(FRAME_TIME, &mut elements);
+    }
+    assert!(autopilot.engaged());
+    run(&mut autopilot, &mut elements, 10);
+    assert!(!elements.is_empty());
+}
@@ -0,0 +1,68 @@
+use c3_led_tail::blend::Blend;
+use smart_leds_trait::RGB8;
+
+const BELOW: RGB8 = RGB8 {
+    r: 200,
+    g: 100,
+    b: 0,
+};
+const COLOR: RGB8 = RGB8 {
+    r: 100,
+    g: 0,
+    b: 200,
+};
+
+#[test]
+fn additive_saturates() {
+    assert_eq!(
+        Blend::Additive.apply(BELOW, COLOR, 255),
+        RGB8 {
+            r: 255,
+            g: 100,
+            b: 199
+        }
+    );
+}
+
+#[test]
+fn max_per_channel() {
+    assert_eq!(
+        Blend::Max.apply(BELOW, COLOR, 255),
+        RGB8 {
+            r: 200,
+            g: 100,
+            b: 199
+        }
+    );
+}
+
+#[test]
+fn alpha_over_covers_at_full_level() {
+    assert_eq!(
+        Blend::AlphaOver.apply(BELOW, COLOR, 255),
+        RGB8 {
+            r: 99,
+            g: 0,
+            b: 199
+        }
+    );
+}
+
+#[test]
+fn alpha_over_mixes_by_level() {
+    assert_eq!(
+        Blend::AlphaOver.apply(BELOW, COLOR, 128),
+        RGB8 {
+            r: 149,
+            g: 49,
+            b: 100
+        }
+    );
+}
+
+#[test]
+fn level_zero_adds_nothing() {
+    for blend in [Blend::Additive, Blend::Max].iter() {
+        assert_eq!(blend.apply(BELOW, COLOR, 0), BELOW);
+    }
+}
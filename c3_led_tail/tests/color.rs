@@ -0,0 +1,68 @@
+use c3_led_tail::color::{to_rgbw, Rgbw, WhitePoint};
+use smart_leds_trait::{White, RGB8, RGBW};
+
+fn rgb(r: u8, g: u8, b: u8) -> RGB8 {
+    RGB8 { r, g, b }
+}
+
+fn rgbw(r: u8, g: u8, b: u8, w: u8) -> RGBW<u8> {
+    RGBW {
+        r,
+        g,
+        b,
+        a: White(w),
+    }
+}
+
+#[test]
+fn white_goes_to_white_die() {
+    assert_eq!(
+        to_rgbw(rgb(255, 255, 255), WhitePoint::NEUTRAL),
+        rgbw(0, 0, 0, 255)
+    );
+}
+
+#[test]
+fn saturated_colors_stay() {
+    for color in [rgb(255, 0, 0), rgb(0, 80, 200)].iter() {
+        let RGB8 { r, g, b } = *color;
+        assert_eq!(to_rgbw(*color, WhitePoint::NEUTRAL), rgbw(r, g, b, 0));
+    }
+}
+
+#[test]
+fn common_part_goes_to_white_die() {
+    assert_eq!(
+        to_rgbw(rgb(200, 100, 50), WhitePoint::NEUTRAL),
+        rgbw(150, 50, 0, 50)
+    );
+}
+
+#[test]
+fn warm_white_point() {
+    let warm = WhitePoint(rgb(255, 200, 100));
+    assert_eq!(to_rgbw(rgb(255, 200, 100), warm), rgbw(0, 0, 0, 255));
+    // Limited by blue, which the warm die has least of
+    assert_eq!(to_rgbw(rgb(255, 255, 50), warm), rgbw(128, 156, 1, 127));
+}
+
+#[test]
+fn white_point_without_a_channel() {
+    let yellow = WhitePoint(rgb(255, 255, 0));
+    assert_eq!(to_rgbw(rgb(100, 100, 100), yellow), rgbw(0, 0, 100, 100));
+    assert_eq!(to_rgbw(rgb(0, 0, 255), yellow), rgbw(0, 0, 255, 0));
+}
+
+#[test]
+fn iterator_converts_each() {
+    let colors = [rgb(255, 255, 255), rgb(255, 0, 0)];
+    let converted: Vec<_> = Rgbw::new(colors.iter().cloned(), WhitePoint::NEUTRAL).collect();
+    assert_eq!(converted, [rgbw(0, 0, 0, 255), rgbw(255, 0, 0, 0)]);
+}
+
+#[test]
+fn white_of_color_adds_to_white_die() {
+    let colors = [rgbw(200, 100, 50, 100), rgbw(255, 0, 0, 255)];
+    let converted: Vec<_> = Rgbw::new(colors.iter().cloned(), WhitePoint::NEUTRAL).collect();
+    assert_eq!(converted, [rgbw(150, 50, 0, 150), rgbw(255, 0, 0, 255)]);
+}
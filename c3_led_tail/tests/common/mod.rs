@@ -0,0 +1,19 @@
+//! The strip the tests run on
+
+pub use c3_led_tail::controller::FRAME_TIME;
+use c3_led_tail::{Elements, QueueElement, Strip};
+use heapless::ArrayLength;
+
+/// A metre with 60 leds and trails of 250 mm, so 15 leds
+pub fn elements<N>() -> Elements<N>
+where
+    N: ArrayLength<QueueElement>,
+{
+    Elements::new(
+        Strip {
+            leds_per_metre: 60,
+            length_mm: 1_000,
+        },
+        250,
+    )
+}
@@ -0,0 +1,254 @@
+mod common;
+
+use c3_led_tail::autopilot::Autopilot;
+use c3_led_tail::consts::*;
+use c3_led_tail::controller::{Controller, Info};
+use c3_protocol::{answer_delay, heartbeat, Address, Board, Identity, Message, MAX_FRAME};
+use common::{elements, FRAME_TIME};
+
+const INFO: Info = Info {
+    board: Board::G071,
+    firmware: [0, 1, 0],
+    leds: 60,
+    effects: 3,
+    max_step_rate: 50,
+};
+
+fn controller() -> Controller<U16> {
+    // No comets of its own while testing
+    let autopilot = Autopilot::with_interval(0, 1_000_000..1_000_001);
+    Controller::new(elements(), autopilot, Identity::new("tail"), INFO)
+}
+
+fn receive(controller: &mut Controller<U16>, address: Address, message: Message, now: u32) {
+    let mut buf = [0; MAX_FRAME];
+    let len = message.encode_to(address, &mut buf).unwrap();
+    for byte in &buf[..len] {
+        controller.receive(*byte, now);
+    }
+}
+
+fn comet() -> Message {
+    Message::Comet {
+        color: [255, 0, 0],
+        white: None,
+        trail_length_mm: 0,
+    }
+}
+
+#[test]
+fn acks_hello_in_its_slot() {
+    let mut controller = controller();
+    let slot = answer_delay(Identity::new("tail").id, 0) as u32;
+    assert_eq!(controller.reply(0), None);
+    receive(&mut controller, Address::Broadcast, Message::Hello, 100);
+    if slot > 0 {
+        assert_eq!(controller.reply(100 + slot - 1), None);
+    }
+    assert_eq!(controller.reply(100 + slot), Some(Message::Ack));
+    assert_eq!(controller.reply(100 + slot), None);
+}
+
+#[test]
+fn status_in_its_slot() {
+    let mut controller = controller();
+    let slot = answer_delay(Identity::new("tail").id, 0) as u32;
+    receive(
+        &mut controller,
+        Address::Broadcast,
+        Message::Identify,
+        1_000,
+    );
+    if slot > 0 {
+        assert_eq!(controller.reply(1_000 + slot - 1), None);
+    }
+    match controller.reply(1_000 + slot) {
+        Some(Message::Status(status)) => {
+            assert_eq!(status.id, Identity::new("tail").id);
+            assert_eq!(status.board, Board::G071);
+            assert_eq!(status.leds, 60);
+            assert_eq!(status.step_rate, (1000 / FRAME_TIME) as u8);
+            assert!(status.commands.supports(&comet()));
+            assert!(!status.commands.supports(&Message::Fade));
+        }
+        x => panic!("Expected a status, got {:?}", x),
+    }
+    assert_eq!(controller.reply(1_000 + slot + 1), None);
+}
+
+#[test]
+fn status_independent_of_step_rate() {
+    let mut controller = controller();
+    let slot = answer_delay(Identity::new("tail").id, 0) as u32;
+    receive(&mut controller, Address::Broadcast, Message::StepRate(1), 0);
+    receive(&mut controller, Address::Broadcast, Message::Identify, 10);
+    assert!(matches!(
+        controller.reply(10 + slot),
+        Some(Message::Status(_))
+    ));
+}
+
+#[test]
+fn slots_change_every_round() {
+    let mut controller = controller();
+    let id = Identity::new("tail").id;
+    for round in 0..4 {
+        let at = 1_000 * (round as u32 + 1);
+        let slot = answer_delay(id, round) as u32;
+        receive(&mut controller, Address::Broadcast, Message::Identify, at);
+        if slot > 0 {
+            assert_eq!(controller.reply(at + slot - 1), None);
+        }
+        assert!(matches!(
+            controller.reply(at + slot),
+            Some(Message::Status(_))
+        ));
+    }
+}
+
+#[test]
+fn heartbeat_without_asking() {
+    let mut controller = controller();
+    let heartbeat = heartbeat(Identity::new("tail").id);
+    assert_eq!(controller.reply(heartbeat - 1), None);
+    assert!(matches!(
+        controller.reply(heartbeat),
+        Some(Message::Status(_))
+    ));
+    assert_eq!(controller.reply(heartbeat + 1), None);
+    // Any status counts as heartbeat
+    receive(
+        &mut controller,
+        Address::Broadcast,
+        Message::Identify,
+        heartbeat + 100,
+    );
+    let answered = heartbeat + 100 + answer_delay(Identity::new("tail").id, 0) as u32;
+    assert!(controller.reply(answered).is_some());
+    assert_eq!(controller.reply(answered + heartbeat - 1), None);
+    assert!(controller.reply(answered + heartbeat).is_some());
+}
+
+#[test]
+fn paces_frames() {
+    let mut controller = controller();
+    let frames = |controller: &mut Controller<U16>, until: u32| {
+        (0..until).filter(|now| controller.frame(*now)).count()
+    };
+    assert_eq!(frames(&mut controller, 1_000), 19);
+    receive(
+        &mut controller,
+        Address::Broadcast,
+        Message::StepRate(10),
+        1_000,
+    );
+    let frames = (1_000..2_000).filter(|now| controller.frame(*now)).count();
+    assert_eq!(frames, 10);
+    assert_eq!(controller.status().step_rate, 10);
+}
+
+#[test]
+fn catches_up_once() {
+    let mut controller = controller();
+    assert!(controller.frame(50));
+    // Late, but the next frame is still on time
+    assert!(controller.frame(130));
+    assert!(!controller.frame(149));
+    assert!(controller.frame(150));
+    // Far behind, so the frames start over from here
+    assert!(controller.frame(1_000));
+    assert!(!controller.frame(1_001));
+    assert!(!controller.frame(1_049));
+    assert!(controller.frame(1_050));
+}
+
+#[test]
+fn counts_failures() {
+    let mut controller = controller();
+    receive(&mut controller, Address::Broadcast, Message::StepRate(0), 0);
+    receive(
+        &mut controller,
+        Address::Broadcast,
+        Message::StepRate(51),
+        0,
+    );
+    receive(&mut controller, Address::Broadcast, Message::Effect(3), 0);
+    assert_eq!(controller.status().failed, 3);
+    assert_eq!(controller.status().step_rate, (1000 / FRAME_TIME) as u8);
+    assert_eq!(controller.effect(), 0);
+    receive(&mut controller, Address::Broadcast, Message::Effect(2), 0);
+    assert_eq!(controller.effect(), 2);
+    assert_eq!(controller.status().failed, 3);
+}
+
+#[test]
+fn counts_corrupted() {
+    let mut controller = controller();
+    let mut buf = [0; MAX_FRAME];
+    let len = Message::Hello.encode(&mut buf).unwrap();
+    buf[1] ^= 0x40;
+    for byte in &buf[..len] {
+        controller.receive(*byte, 0);
+    }
+    assert_eq!(controller.status().corrupted, 1);
+    assert_eq!(controller.reply(0), None);
+}
+
+#[test]
+fn adds_comets() {
+    let mut controller = controller();
+    receive(&mut controller, Address::Broadcast, Message::Effect(1), 0);
+    receive(&mut controller, Address::Broadcast, comet(), 0);
+    assert_eq!(controller.elements_mut().len(), 1);
+    // The host took over, so its effect stays
+    assert!(controller.frame(50));
+    assert_eq!(controller.effect(), 1);
+}
+
+#[test]
+fn autopilot_takes_over() {
+    let mut controller = controller();
+    receive(&mut controller, Address::Broadcast, Message::Effect(1), 0);
+    for now in 0..=10_000 {
+        // Asking for the status doesn't count as hearing from the host
+        if now == 9_000 {
+            receive(&mut controller, Address::Broadcast, Message::Identify, now);
+        }
+        controller.frame(now);
+        if now < 10_000 {
+            assert_eq!(controller.effect(), 1);
+        }
+    }
+    // The autopilot only adds comets, so it shows them
+    assert_eq!(controller.effect(), 0);
+}
+
+#[test]
+fn skips_other_devices() {
+    let mut controller = controller();
+    receive(&mut controller, Address::device("other"), comet(), 0);
+    receive(&mut controller, Address::group("others"), comet(), 0);
+    assert!(controller.elements_mut().is_empty());
+    receive(&mut controller, Address::device("tail"), comet(), 0);
+    assert_eq!(controller.elements_mut().len(), 1);
+}
+
+#[test]
+fn dims() {
+    let mut controller = controller();
+    assert_eq!(controller.brightness(), 256);
+    receive(
+        &mut controller,
+        Address::Broadcast,
+        Message::Brightness(0),
+        0,
+    );
+    assert_eq!(controller.brightness(), 0);
+    receive(
+        &mut controller,
+        Address::Broadcast,
+        Message::Brightness(255),
+        0,
+    );
+    assert_eq!(controller.brightness(), 256);
+}
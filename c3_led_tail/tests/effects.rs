@@ -0,0 +1,50 @@
+use c3_led_tail::consts::*;
+use c3_led_tail::effects::*;
+use c3_led_tail::{Elements, Strip, COLORS};
+use smart_leds_trait::RGB8;
+
+#[test]
+fn zero_fields_dont_panic() {
+    let mut leds = [RGB8::default(); 10];
+    let mut rainbow = Rainbow {
+        period: 0,
+        spread: 0,
+    };
+    let mut breathing = Breathing {
+        color: COLORS[0],
+        period: 0,
+    };
+    let mut chase = TheaterChase {
+        color: COLORS[0],
+        spacing: 0,
+        period: 0,
+    };
+    let mut effects: [&mut dyn Effect; 3] = [&mut rainbow, &mut breathing, &mut chase];
+    for effect in effects.iter_mut() {
+        for time in 0..3 {
+            effect.render(time, &mut leds);
+        }
+    }
+}
+
+#[test]
+fn effects_in_order() {
+    let mut effects: Effects<U16> = Effects::new(1);
+    let mut comets: Elements<U16> = Elements::new(
+        Strip {
+            leds_per_metre: 60,
+            length_mm: 250,
+        },
+        250,
+    );
+    let mut leds = [RGB8::default(); 15];
+    for n in 0..EFFECTS {
+        effects.render(n, &mut comets, 1_000, &mut leds);
+    }
+    // The comets come first, there are none
+    effects.render(0, &mut comets, 1_000, &mut leds);
+    assert!(leds.iter().all(|x| *x == RGB8::default()));
+    // The breathing lights the whole strip at once
+    effects.render(2, &mut comets, 1_000, &mut leds);
+    assert!(leds.iter().all(|x| *x == leds[0] && *x != RGB8::default()));
+}
@@ -0,0 +1,49 @@
+use c3_led_tail::falloff::Falloff;
+
+const CURVES: [Falloff; 3] = [Falloff::Linear, Falloff::Quadratic, Falloff::Exponential];
+
+#[test]
+fn full_at_head_and_off_at_end() {
+    for curve in CURVES.iter() {
+        assert_eq!(curve.apply(255), 255);
+        assert_eq!(curve.apply(0), 0);
+    }
+}
+
+#[test]
+fn never_brighter_further_back() {
+    for curve in CURVES.iter() {
+        for level in 1..=255 {
+            assert!(curve.apply(level - 1) <= curve.apply(level));
+        }
+    }
+}
+
+#[test]
+fn quadratic_below_linear() {
+    for level in 1..255 {
+        assert!(Falloff::Quadratic.apply(level) < Falloff::Linear.apply(level));
+    }
+    assert_eq!(Falloff::Quadratic.apply(128), 64);
+}
+
+#[test]
+fn exponential_halves_every_eighth() {
+    assert_eq!(Falloff::Exponential.apply(255 - 32), 127);
+    assert_eq!(Falloff::Exponential.apply(255 - 64), 63);
+    assert_eq!(Falloff::Exponential.apply(255 - 96), 31);
+}
+
+#[test]
+fn lut_is_looked_up() {
+    static STEPS: [u8; 256] = {
+        let mut lut = [0; 256];
+        lut[255] = 255;
+        lut[200] = 100;
+        lut
+    };
+    let curve = Falloff::Lut(&STEPS);
+    assert_eq!(curve.apply(255), 255);
+    assert_eq!(curve.apply(200), 100);
+    assert_eq!(curve.apply(199), 0);
+}
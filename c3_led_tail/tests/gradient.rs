@@ -0,0 +1,50 @@
+mod common;
+
+use c3_led_tail::consts::*;
+use c3_led_tail::{Elements, Velocity};
+use common::{elements, FRAME_TIME};
+use smart_leds_trait::RGB8;
+
+const RED: RGB8 = RGB8 { r: 255, g: 0, b: 0 };
+const BLUE: RGB8 = RGB8 { r: 0, g: 0, b: 255 };
+
+/// Frame with a red to blue comet at led 30, its trail reaching back to led 15
+fn frame() -> Vec<RGB8> {
+    let mut elements: Elements<U16> = elements();
+    elements.add_gradient(RED, BLUE, Velocity(500)).unwrap();
+    for _ in 0..20 {
+        elements.step(FRAME_TIME);
+    }
+    elements.iter().collect()
+}
+
+#[test]
+fn head_has_head_color() {
+    let frame = frame();
+    assert_eq!(frame[30], RGB8 { r: 254, g: 0, b: 0 });
+}
+
+#[test]
+fn fades_into_tail_color() {
+    let frame = frame();
+    assert!(frame[17].b > frame[17].r);
+    // The share of blue grows towards the end of the trail
+    for i in 17..30 {
+        let share = |x: RGB8| x.b as u32 * 256 / (x.r as u32 + x.b as u32);
+        assert!(share(frame[i]) >= share(frame[i + 1]));
+    }
+}
+
+#[test]
+fn same_colors_like_add() {
+    let (mut plain, mut gradient): (Elements<U16>, Elements<U16>) = (elements(), elements());
+    plain.add(RED).unwrap();
+    gradient
+        .add_gradient(RED, RED, Velocity::default())
+        .unwrap();
+    for _ in 0..20 {
+        plain.step(FRAME_TIME);
+        gradient.step(FRAME_TIME);
+    }
+    assert!(plain.iter().eq(gradient.iter()));
+}
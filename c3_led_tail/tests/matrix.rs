@@ -0,0 +1,49 @@
+use c3_led_tail::matrix::{Corner, Layout, Wiring};
+
+fn wiring(layout: Layout, start: Corner) -> Wiring {
+    Wiring {
+        width: 4,
+        height: 3,
+        layout,
+        start,
+    }
+}
+
+/// Indices of all leds, row by row from the top left
+fn indices(wiring: Wiring) -> Vec<usize> {
+    (0..wiring.height)
+        .flat_map(|y| (0..wiring.width).map(move |x| wiring.index(x, y).unwrap()))
+        .collect()
+}
+
+#[test]
+fn progressive() {
+    let wiring = wiring(Layout::Progressive, Corner::TopLeft);
+    assert_eq!(indices(wiring), (0..12).collect::<Vec<_>>());
+}
+
+#[test]
+fn serpentine() {
+    let wiring = wiring(Layout::Serpentine, Corner::TopLeft);
+    assert_eq!(indices(wiring), [0, 1, 2, 3, 7, 6, 5, 4, 8, 9, 10, 11]);
+}
+
+#[test]
+fn starting_in_other_corners() {
+    let top_right = wiring(Layout::Serpentine, Corner::TopRight);
+    assert_eq!(indices(top_right), [3, 2, 1, 0, 4, 5, 6, 7, 11, 10, 9, 8]);
+    let bottom_left = wiring(Layout::Progressive, Corner::BottomLeft);
+    assert_eq!(indices(bottom_left), [8, 9, 10, 11, 4, 5, 6, 7, 0, 1, 2, 3]);
+    let bottom_right = wiring(Layout::Serpentine, Corner::BottomRight);
+    assert_eq!(
+        indices(bottom_right),
+        [11, 10, 9, 8, 4, 5, 6, 7, 3, 2, 1, 0]
+    );
+}
+
+#[test]
+fn outside_of_matrix() {
+    let wiring = wiring(Layout::Progressive, Corner::TopLeft);
+    assert_eq!(wiring.index(4, 0), None);
+    assert_eq!(wiring.index(0, 3), None);
+}
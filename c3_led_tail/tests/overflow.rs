@@ -0,0 +1,59 @@
+mod common;
+
+use c3_led_tail::consts::*;
+use c3_led_tail::{Elements, Error, Overflow};
+use common::{elements, FRAME_TIME};
+use smart_leds_trait::RGB8;
+
+const RED: RGB8 = RGB8 { r: 255, g: 0, b: 0 };
+const GREEN: RGB8 = RGB8 { r: 0, g: 255, b: 0 };
+const BLUE: RGB8 = RGB8 { r: 0, g: 0, b: 255 };
+
+/// Strip with space for two comets, which got red, green and blue added
+fn overflowed(overflow: Overflow) -> (Elements<U2>, Result<(), Error>) {
+    let mut elements = elements();
+    elements.set_overflow(overflow);
+    elements.add(RED).unwrap();
+    elements.add(GREEN).unwrap();
+    let added = elements.add(BLUE);
+    (elements, added)
+}
+
+/// Whether any led has some of red, green and blue while the comets cross
+/// the strip
+fn shown(elements: &mut Elements<U2>) -> [bool; 3] {
+    let mut shown = [false; 3];
+    while !elements.is_empty() {
+        elements.step(FRAME_TIME);
+        for x in elements.iter() {
+            shown[0] |= x.r != 0;
+            shown[1] |= x.g != 0;
+            shown[2] |= x.b != 0;
+        }
+    }
+    shown
+}
+
+#[test]
+fn drop_newest() {
+    let (mut elements, added) = overflowed(Overflow::DropNewest);
+    assert_eq!(added, Err(Error::QueueFull));
+    assert_eq!(elements.len(), 2);
+    assert_eq!(shown(&mut elements), [true, true, false]);
+}
+
+#[test]
+fn drop_oldest() {
+    let (mut elements, added) = overflowed(Overflow::DropOldest);
+    assert_eq!(added, Ok(()));
+    assert_eq!(elements.len(), 2);
+    assert_eq!(shown(&mut elements), [false, true, true]);
+}
+
+#[test]
+fn merge_into_last() {
+    let (mut elements, added) = overflowed(Overflow::MergeIntoLast);
+    assert_eq!(added, Ok(()));
+    assert_eq!(elements.len(), 2);
+    assert_eq!(shown(&mut elements), [true, false, true]);
+}
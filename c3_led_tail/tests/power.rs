@@ -0,0 +1,54 @@
+use c3_led_tail::power::{ChipModel, Dim, PowerLimit};
+use smart_leds_trait::{White, RGB8, RGBW};
+
+const LIMIT: PowerLimit = PowerLimit {
+    model: ChipModel::WS2812,
+    budget_ma: 2_000,
+};
+const WHITE: RGB8 = RGB8 {
+    r: 255,
+    g: 255,
+    b: 255,
+};
+
+#[test]
+fn within_budget_unchanged() {
+    let frame = [WHITE; 10];
+    assert_eq!(LIMIT.current_ma(frame.iter().cloned()), 610);
+    assert_eq!(LIMIT.scale(frame.iter().cloned()), 256);
+}
+
+#[test]
+fn over_budget_dimmed_into_it() {
+    let frame = [WHITE; 100];
+    assert_eq!(LIMIT.current_ma(frame.iter().cloned()), 6_100);
+    let scale = LIMIT.scale(frame.iter().cloned());
+    assert_eq!(scale, 81);
+    let dimmed = Dim::new(frame.iter().cloned(), scale);
+    let current = LIMIT.current_ma(dimmed);
+    assert!(current <= 2_000 && current > 1_900);
+}
+
+#[test]
+fn idle_current_over_budget() {
+    let limit = PowerLimit {
+        budget_ma: 50,
+        ..LIMIT
+    };
+    let frame = [WHITE; 100];
+    assert_eq!(limit.scale(frame.iter().cloned()), 0);
+    // Nothing to dim
+    let dark = [RGB8::default(); 100];
+    assert_eq!(limit.scale(dark.iter().cloned()), 256);
+}
+
+#[test]
+fn white_die_counts() {
+    let frame = [RGBW {
+        r: 0,
+        g: 0,
+        b: 0,
+        a: White(255),
+    }; 10];
+    assert_eq!(LIMIT.current_ma(frame.iter().cloned()), 210);
+}
@@ -0,0 +1,63 @@
+use c3_led_tail::segment::Segment;
+use smart_leds_trait::RGB8;
+
+/// Frame whose leds have their index as red
+fn frame(len: u8) -> Vec<RGB8> {
+    (0..len).map(|r| RGB8 { r, g: 0, b: 0 }).collect()
+}
+
+fn reds(segment: Segment, frame: &[RGB8]) -> Vec<u8> {
+    segment.map(frame).map(|x| x.r).collect()
+}
+
+#[test]
+fn forward() {
+    let segment = Segment {
+        start: 2,
+        length: 3,
+        reversed: false,
+    };
+    assert_eq!(reds(segment, &frame(10)), [2, 3, 4]);
+}
+
+#[test]
+fn reversed() {
+    let segment = Segment {
+        start: 2,
+        length: 3,
+        reversed: true,
+    };
+    assert_eq!(reds(segment, &frame(10)), [4, 3, 2]);
+}
+
+#[test]
+fn dark_past_end_of_frame() {
+    let frame = frame(10);
+    let segment = Segment {
+        start: 8,
+        length: 4,
+        reversed: false,
+    };
+    let colors: Vec<_> = segment.map(&frame).collect();
+    assert_eq!(colors.len(), 4);
+    assert_eq!(colors[1].r, 9);
+    assert_eq!(&colors[2..], [RGB8::default(); 2]);
+}
+
+#[test]
+fn runs_with_a_gap() {
+    // Two runs of 3 leds, 2 leds apart, the second one wired backwards
+    let frame = frame(8);
+    let first = Segment {
+        start: 0,
+        length: 3,
+        reversed: false,
+    };
+    let second = Segment {
+        start: 5,
+        length: 3,
+        reversed: true,
+    };
+    assert_eq!(reds(first, &frame), [0, 1, 2]);
+    assert_eq!(reds(second, &frame), [7, 6, 5]);
+}
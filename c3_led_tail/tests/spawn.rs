@@ -0,0 +1,85 @@
+mod common;
+
+use c3_led_tail::consts::*;
+use c3_led_tail::{Elements, Error, Overflow};
+use common::FRAME_TIME;
+use smart_leds_trait::RGB8;
+
+const RED: RGB8 = RGB8 { r: 255, g: 0, b: 0 };
+const GREEN: RGB8 = RGB8 { r: 0, g: 255, b: 0 };
+
+fn elements() -> Elements<U4> {
+    let mut elements = common::elements();
+    // 6 leds
+    elements.set_spacing(100);
+    elements
+}
+
+fn shows_green(elements: &mut Elements<U4>) -> bool {
+    elements.iter().any(|x| x.g != 0)
+}
+
+#[test]
+fn waits_for_spacing() {
+    let mut elements = elements();
+    elements.add(RED).unwrap();
+    elements.add(GREEN).unwrap();
+    assert_eq!(elements.len(), 2);
+    assert!(!shows_green(&mut elements));
+    // 100 mm at 333 mm/s take 300 ms
+    for _ in 0..5 {
+        elements.step(FRAME_TIME);
+        assert!(!shows_green(&mut elements));
+    }
+    elements.step(FRAME_TIME);
+    elements.step(FRAME_TIME);
+    assert!(shows_green(&mut elements));
+    assert_eq!(elements.len(), 2);
+}
+
+#[test]
+fn spawns_in_order() {
+    let mut elements = elements();
+    elements.add(RED).unwrap();
+    elements.add(RED).unwrap();
+    elements.add(GREEN).unwrap();
+    // The second red is spawned after 300 ms and the green one 300 ms later
+    for _ in 0..10 {
+        elements.step(FRAME_TIME);
+        assert!(!shows_green(&mut elements));
+    }
+    for _ in 0..5 {
+        elements.step(FRAME_TIME);
+    }
+    assert!(shows_green(&mut elements));
+}
+
+#[test]
+fn pending_comets_count_towards_capacity() {
+    let mut elements = elements();
+    elements.set_overflow(Overflow::DropNewest);
+    for _ in 0..4 {
+        elements.add(RED).unwrap();
+    }
+    // Three of them are still waiting
+    assert_eq!(elements.add(GREEN), Err(Error::QueueFull));
+    assert_eq!(elements.len(), 4);
+    for _ in 0..100 {
+        elements.step(FRAME_TIME);
+        assert!(!shows_green(&mut elements));
+    }
+}
+
+#[test]
+fn pending_comets_leave_eventually() {
+    let mut elements = elements();
+    for _ in 0..4 {
+        elements.add(RED).unwrap();
+    }
+    // Four comets 6 leds apart, the last one needs another 18 leds and 75
+    // more to leave
+    for _ in 0..200 {
+        elements.step(FRAME_TIME);
+    }
+    assert!(elements.is_empty());
+}
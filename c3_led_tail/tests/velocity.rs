@@ -0,0 +1,86 @@
+mod common;
+
+use c3_led_tail::consts::*;
+use c3_led_tail::{Elements, Velocity};
+use common::{elements, FRAME_TIME};
+use smart_leds_trait::RGB8;
+
+const WHITE: RGB8 = RGB8 {
+    r: 255,
+    g: 255,
+    b: 255,
+};
+
+/// Indices of the leds that are on
+fn lit(elements: &mut Elements<U16>) -> Vec<usize> {
+    elements
+        .iter()
+        .enumerate()
+        .filter(|(_, x)| *x != RGB8::default())
+        .map(|(i, _)| i)
+        .collect()
+}
+
+#[test]
+fn forward_starts_at_start() {
+    let mut elements: Elements<U16> = elements();
+    // 1.5 leds per frame
+    elements.add_with_velocity(WHITE, Velocity(500)).unwrap();
+    for _ in 0..20 {
+        elements.step(FRAME_TIME);
+    }
+    assert_eq!(lit(&mut elements).last(), Some(&30));
+}
+
+#[test]
+fn backward_starts_at_end() {
+    let mut elements: Elements<U16> = elements();
+    elements.add_with_velocity(WHITE, Velocity(-500)).unwrap();
+    for _ in 0..20 {
+        elements.step(FRAME_TIME);
+    }
+    assert_eq!(lit(&mut elements).first(), Some(&29));
+}
+
+#[test]
+fn faster_comets_get_further() {
+    let mut slow: Elements<U16> = elements();
+    let mut fast: Elements<U16> = elements();
+    slow.add_with_velocity(WHITE, Velocity(250)).unwrap();
+    fast.add_with_velocity(WHITE, Velocity(500)).unwrap();
+    for _ in 0..20 {
+        slow.step(FRAME_TIME);
+        fast.step(FRAME_TIME);
+    }
+    assert_eq!(lit(&mut slow).last(), Some(&15));
+    assert_eq!(lit(&mut fast).last(), Some(&30));
+}
+
+#[test]
+fn gone_once_trail_left() {
+    for velocity in [Velocity(500), Velocity(-500)].iter() {
+        let mut elements: Elements<U16> = elements();
+        elements.add_with_velocity(WHITE, *velocity).unwrap();
+        // 60 leds of the strip and 15 of the trail
+        for _ in 0..48 {
+            elements.step(FRAME_TIME);
+        }
+        assert!(!lit(&mut elements).is_empty());
+        for _ in 0..2 {
+            elements.step(FRAME_TIME);
+        }
+        assert!(lit(&mut elements).is_empty());
+    }
+}
+
+#[test]
+fn slow_comets_move() {
+    let mut elements: Elements<U16> = elements();
+    // Less than a step of the fixed point position per frame
+    elements.add_with_velocity(WHITE, Velocity(1)).unwrap();
+    for _ in 0..2_000 {
+        elements.step(FRAME_TIME);
+    }
+    // 100 mm at 60 leds/m
+    assert_eq!(lit(&mut elements).last(), Some(&6));
+}
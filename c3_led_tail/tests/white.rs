@@ -0,0 +1,56 @@
+mod common;
+
+use c3_led_tail::consts::*;
+use c3_led_tail::Elements;
+use common::{elements, FRAME_TIME};
+use smart_leds_trait::{White, RGB8, RGBW};
+
+const RED_AND_WHITE: RGBW<u8> = RGBW {
+    r: 255,
+    g: 0,
+    b: 0,
+    a: White(255),
+};
+
+/// A red comet with full white, about halfway along the strip
+fn elements_with_white() -> Elements<U16> {
+    let mut elements = elements();
+    elements.add_rgbw(RED_AND_WHITE, None).unwrap();
+    for _ in 0..30 {
+        elements.step(FRAME_TIME);
+    }
+    elements
+}
+
+#[test]
+fn white_die_keeps_white() {
+    let mut elements = elements_with_white();
+    let frame: Vec<_> = elements.iter_rgbw().collect();
+    assert!(frame.iter().any(|x| x.a.0 > 200));
+    // Fades along the trail like the color, which stays red
+    for x in frame {
+        assert_eq!(x.a.0, x.r);
+        assert_eq!((x.g, x.b), (0, 0));
+    }
+}
+
+#[test]
+fn mixed_into_rgb() {
+    let mut elements = elements_with_white();
+    let frame: Vec<_> = elements.iter().collect();
+    assert!(frame.iter().any(|x| x.g > 200));
+    for x in frame {
+        assert_eq!(x.g, x.b);
+        assert_eq!(x.r, x.g.saturating_mul(2));
+    }
+}
+
+#[test]
+fn comets_without_white() {
+    let mut elements: Elements<U16> = elements();
+    elements.add(RGB8 { r: 0, g: 0, b: 255 }).unwrap();
+    for _ in 0..30 {
+        elements.step(FRAME_TIME);
+    }
+    assert!(elements.iter_rgbw().all(|x| x.a == White(0)));
+}
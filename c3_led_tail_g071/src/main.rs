@@ -7,20 +7,49 @@ use panic_semihosting;
 use stm32f0xx_hal as hal;
 use ws2812_spi as ws2812;
 
-use crate::hal::delay::Delay;
 use crate::hal::prelude::*;
-use crate::hal::serial::Serial;
+use crate::hal::serial::{Event, Rx, Serial, Tx};
 use crate::hal::spi::Spi;
-use crate::hal::time::Hertz;
-use crate::hal::timers::Timer;
-use nb::block;
-use smart_leds::SmartLedsWrite;
+use heapless::spsc::{Consumer, Producer, Queue};
+use smart_leds::{SmartLedsWrite, White, RGB8, RGBW};
 
-use c3_led_tail::Elements;
+use c3_led_tail::autopilot::Autopilot;
+use c3_led_tail::clock::Clock;
+use c3_led_tail::color::{Rgbw, WhitePoint};
+use c3_led_tail::consts::*;
+use c3_led_tail::controller::{Controller, Info};
+use c3_led_tail::effects::*;
+use c3_led_tail::power::{ChipModel, Dim, PowerLimit};
+use c3_led_tail::seed;
+use c3_led_tail::{Elements, Strip};
+use c3_protocol::{version, Board, Identity};
+
+// Frequency of the core, which the clock counts
+const CORE_HZ: u32 = 48_000_000;
+// What the supply of the sk6812w strip can deliver
+const POWER_LIMIT: PowerLimit = PowerLimit {
+    model: ChipModel::SK6812W,
+    budget_ma: 2_000,
+};
+// Color of the white die on the sk6812w
+const WHITE_POINT: WhitePoint = WhitePoint::NEUTRAL;
 
 use hal::gpio::gpioa::*;
 use hal::gpio::*;
 
+// Addresses of the words of the stm32f042 unique id
+const UNIQUE_ID: [usize; 3] = [0x1FFF_F7AC, 0x1FFF_F7B0, 0x1FFF_F7B4];
+
+fn unique_id() -> [u8; 12] {
+    let mut id = [0; 12];
+    for (i, address) in UNIQUE_ID.iter().enumerate() {
+        // NOTE(unsafe): Read only register
+        let word = unsafe { core::ptr::read_volatile(*address as *const u32) };
+        id[i * 4..i * 4 + 4].copy_from_slice(&word.to_le_bytes());
+    }
+    id
+}
+
 #[rtfm::app(device = stm32f0xx_hal::stm32, peripherals = true)]
 const APP: () = {
     struct Resources {
@@ -28,31 +57,37 @@ const APP: () = {
             Spi<hal::stm32::SPI1, PA5<Alternate<AF0>>, PA6<Alternate<AF0>>, PA7<Alternate<AF0>>>,
             ws2812::devices::Sk6812w,
         >,
-        delay: hal::delay::Delay,
-        timer: Timer<hal::stm32::TIM1>,
-        serial: Serial<hal::stm32::USART2, PA2<Alternate<AF1>>, PA3<Alternate<AF1>>>,
+        clock: Clock,
+        rx: Rx<hal::stm32::USART2>,
+        tx: Tx<hal::stm32::USART2>,
+        // Bytes from the host, received while a frame is written
+        received: Producer<'static, u8, U64>,
+        to_controller: Consumer<'static, u8, U64>,
     }
 
     #[init]
     fn init(context: init::Context) -> init::LateResources {
+        static mut RECEIVED: Queue<u8, U64> = Queue(heapless::i::Queue::new());
+
         // NOTE(unsafe): Safe, since rtfm guarantees that no interrupts run in init
         let cs = unsafe { cortex_m::interrupt::CriticalSection::new() };
 
         let p = context.device;
         let mut flash = p.FLASH;
-        let mut rcc = p.RCC.configure().sysclk(48.mhz()).freeze(&mut flash);
+        let mut rcc = p.RCC.configure().sysclk(CORE_HZ.hz()).freeze(&mut flash);
         let gpioa = p.GPIOA.split(&mut rcc);
         let (sck, miso, mosi, tx, rx) = (
             // SPI
             gpioa.pa5.into_alternate_af0(&cs),
             gpioa.pa6.into_alternate_af0(&cs),
             gpioa.pa7.into_alternate_af0(&cs),
-            // Serial
+            // Serial, tx is push-pull, so it needs a diode to share the line
+            // back to the host, see the README
             gpioa.pa2.into_alternate_af1(&cs),
             gpioa.pa3.into_alternate_af1(&cs),
         );
 
-        let delay = Delay::new(context.core.SYST, &mut rcc);
+        let clock = Clock::new(context.core.SYST, CORE_HZ);
 
         let spi = Spi::spi1(
             p.SPI1,
@@ -62,57 +97,104 @@ const APP: () = {
             &mut rcc,
         );
 
-        let timer = Timer::tim1(p.TIM1, Hertz(20), &mut rcc);
-        let serial = Serial::usart2(p.USART2, (tx, rx), 9600.bps(), &mut rcc);
+        let mut serial = Serial::usart2(p.USART2, (tx, rx), 9600.bps(), &mut rcc);
+        serial.listen(Event::Rxne);
+        let (tx, rx) = serial.split();
+        let (received, to_controller) = RECEIVED.split();
 
         let ws = ws2812::Ws2812::new_sk6812w(spi);
         init::LateResources {
             ws,
-            delay,
-            timer,
-            serial,
+            clock,
+            rx,
+            tx,
+            received,
+            to_controller,
         }
     }
 
-    #[idle(resources = [ws, delay, timer, serial])]
+    #[task(binds = USART2, resources = [rx, received])]
+    fn receive(c: receive::Context) {
+        if let Ok(byte) = c.resources.rx.read() {
+            // Dropped if idle is that far behind, like a corrupted frame
+            c.resources.received.enqueue(byte).ok();
+        }
+    }
+
+    #[idle(resources = [ws, clock, tx, to_controller])]
     fn idle(c: idle::Context) -> ! {
         // Matching resources in c3_display
-        let mut elements = Elements::new(400, 15);
-        // Chosen by fair dice roll
-        let mut rand = oorandom::Rand32::new(0);
-        // On average add a new color every 15 steps
-        let mut steps = rand.rand_range(10..20);
-        // Do something when host isn't active yet
-        // Drops first byte
-        while c.resources.serial.read().is_err() {
-            steps -= 1;
-            if steps == 0 {
-                steps = rand.rand_range(10..20);
-                elements
-                    .add_predefined(rand.rand_range(0..c3_led_tail::COLORS.len() as u32) as usize)
-                    .unwrap();
-            }
-            block!(c.resources.timer.wait()).unwrap();
-        }
-        // Host driven mode
+        // At most one color every 500 ms, so this is plenty
+        let mut elements: Elements<U64> = Elements::new(
+            Strip {
+                leds_per_metre: 60,
+                length_mm: 6_667,
+            },
+            250,
+        );
+        // Differs between devices, so the strips don't run in lockstep
+        let seed = seed::configured().unwrap_or_else(|| seed::from_id(&unique_id()));
+        let mut effects: Effects<U400> = Effects::new(seed);
+        let mut frame = [RGB8::default(); 400];
+        // The white of the comets, which is left to the white die
+        let mut white = [0u8; 400];
+        // Do something until the host is active or when it's gone quiet
+        let autopilot = Autopilot::new(seed);
+        // Messages for other devices are skipped
+        let mut identity = Identity::configured()
+            .unwrap_or_else(|| Identity::from_unique_id(&unique_id()).with_configured_groups());
+        identity.join("tails").ok();
+        let info = Info {
+            board: Board::G071,
+            firmware: version(env!("CARGO_PKG_VERSION")),
+            leds: frame.len() as u16,
+            effects: EFFECTS,
+            max_step_rate: 50,
+        };
+        let mut controller = Controller::new(elements, autopilot, identity, info);
         loop {
-            if let Ok(byte) = c.resources.serial.read().map(|x| x as usize) {
-                if byte < c3_led_tail::COLORS.len() {
-                    elements.add_predefined(byte).unwrap();
-                }
+            let now = c.resources.clock.now();
+            while let Some(byte) = c.resources.to_controller.dequeue() {
+                controller.receive(byte, now);
+            }
+            if let Some(reply) = controller.reply(now) {
+                reply.send(c.resources.tx).ok();
             }
-            if c.resources.timer.wait().is_ok() {
-                elements.step();
+            if controller.frame(now) {
+                let (effect, time) = (controller.effect(), controller.time());
+                if effect == 0 {
+                    let leds = frame.iter_mut().zip(white.iter_mut());
+                    for ((led, w), x) in leds.zip(controller.elements_mut().iter_rgbw()) {
+                        *led = RGB8 {
+                            r: x.r,
+                            g: x.g,
+                            b: x.b,
+                        };
+                        *w = x.a.0;
+                    }
+                } else {
+                    effects.render(effect, controller.elements_mut(), time, &mut frame);
+                    white = [0; 400];
+                }
+                let brightness = controller.brightness();
+                let colors = || {
+                    // Same curve for the white die
+                    let white = white.iter().map(|&w| RGB8 { r: w, g: w, b: w });
+                    let leds = smart_leds::gamma(frame.iter().cloned())
+                        .zip(smart_leds::gamma(white))
+                        .map(|(x, w)| RGBW {
+                            r: x.r,
+                            g: x.g,
+                            b: x.b,
+                            a: White(w.r),
+                        });
+                    Rgbw::new(leds, WHITE_POINT)
+                };
+                // Both dim linearly, so the lower one stays within the budget
+                let scale = POWER_LIMIT.scale(colors()).min(brightness);
                 c.resources
                     .ws
-                    .write(
-                        smart_leds::gamma(elements.iter()).map(|e| smart_leds::RGBW {
-                            r: e.r,
-                            g: e.g,
-                            b: e.b,
-                            a: smart_leds::White(0),
-                        }),
-                    )
+                    .write(Dim::new(colors(), scale))
                     .expect("Write");
             }
         }
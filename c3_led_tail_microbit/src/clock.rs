@@ -0,0 +1,54 @@
+//! Milliseconds since the start from TIMER1, the nrf51 has no SysTick
+
+use microbit::hal::nrf51::TIMER1;
+
+// 16 MHz / 2^9
+const PRESCALER: u8 = 9;
+// 125 ticks of the timer at 31250 Hz are 4 ms
+const TICKS: u32 = 125;
+const MS: u32 = 4;
+
+/// Counts milliseconds with the free running TIMER1
+///
+/// TIMER1 only counts up to 16 bits and wraps every 2 s at 31250 Hz, `now`
+/// has to be called more often than that. Same interface as
+/// `c3_led_tail::clock::Clock`.
+pub struct Clock {
+    timer: TIMER1,
+    // Counter at the last `now`
+    last: u16,
+    // Time not counted as a full ms yet, in 1/125 ms
+    rest: u32,
+    ms: u32,
+}
+
+impl Clock {
+    pub fn new(timer: TIMER1) -> Self {
+        timer.tasks_stop.write(|w| unsafe { w.bits(1) });
+        timer.mode.write(|w| w.mode().timer());
+        timer.bitmode.write(|w| w.bitmode()._16bit());
+        timer
+            .prescaler
+            .write(|w| unsafe { w.prescaler().bits(PRESCALER) });
+        timer.tasks_clear.write(|w| unsafe { w.bits(1) });
+        timer.tasks_start.write(|w| unsafe { w.bits(1) });
+        Self {
+            timer,
+            last: 0,
+            rest: 0,
+            ms: 0,
+        }
+    }
+
+    /// Milliseconds since the clock was started, wrapping after 49 days
+    pub fn now(&mut self) -> u32 {
+        // The counter can only be read by capturing it
+        self.timer.tasks_capture[0].write(|w| unsafe { w.bits(1) });
+        let current = self.timer.cc[0].read().bits() as u16;
+        self.rest += current.wrapping_sub(self.last) as u32 * MS;
+        self.last = current;
+        self.ms = self.ms.wrapping_add(self.rest / TICKS);
+        self.rest %= TICKS;
+        self.ms
+    }
+}
@@ -9,14 +9,44 @@ use ws2812_timer_delay as ws2812;
 
 use hal::hi_res_timer::TimerFrequency;
 use hal::prelude::*;
-use hal::serial::{Rx, Serial};
+use hal::serial::{Rx, Serial, Tx};
 use hal::spi::{self, Spi};
 use hal::time::Hfticks;
 use hal::timer::CountDownTimer;
-use nb::block;
-use smart_leds::SmartLedsWrite;
+use heapless::spsc::{Consumer, Producer, Queue};
+use smart_leds::{SmartLedsWrite, RGB8};
 
-use c3_led_tail::Elements;
+use c3_led_tail::autopilot::Autopilot;
+use c3_led_tail::consts::*;
+use c3_led_tail::controller::{Controller, Info};
+use c3_led_tail::effects::*;
+use c3_led_tail::power::{ChipModel, Dim, PowerLimit};
+use c3_led_tail::seed;
+use c3_led_tail::{Elements, Strip};
+use c3_protocol::{version, Board, Identity};
+
+use crate::clock::Clock;
+
+mod clock;
+
+// What the supply of the ws2812 strip can deliver
+const POWER_LIMIT: PowerLimit = PowerLimit {
+    model: ChipModel::WS2812,
+    budget_ma: 2_000,
+};
+
+// Addresses of the words of the nrf51 device id, in the FICR
+const UNIQUE_ID: [usize; 2] = [0x1000_0060, 0x1000_0064];
+
+fn unique_id() -> [u8; 8] {
+    let mut id = [0; 8];
+    for (i, address) in UNIQUE_ID.iter().enumerate() {
+        // NOTE(unsafe): Read only register
+        let word = unsafe { core::ptr::read_volatile(*address as *const u32) };
+        id[i * 4..i * 4 + 4].copy_from_slice(&word.to_le_bytes());
+    }
+    id
+}
 
 #[rtfm::app(device = microbit, peripherals = true)]
 const APP: () = {
@@ -25,12 +55,18 @@ const APP: () = {
             CountDownTimer<hal::nrf51::TIMER0>,
             hal::gpio::gpio::PIN21<hal::gpio::Output<hal::gpio::PushPull>>,
         >,
-        timer: CountDownTimer<hal::nrf51::TIMER1>,
-        serial: Rx<hal::nrf51::UART0>,
+        clock: Clock,
+        rx: Rx<hal::nrf51::UART0>,
+        tx: Tx<hal::nrf51::UART0>,
+        // Bytes from the host, received while a frame is written
+        received: Producer<'static, u8, U64>,
+        to_controller: Consumer<'static, u8, U64>,
     }
 
     #[init]
     fn init(context: init::Context) -> init::LateResources {
+        static mut RECEIVED: Queue<u8, U64> = Queue(heapless::i::Queue::new());
+
         let p = context.device;
 
         let gpio = p.GPIO.split();
@@ -38,6 +74,7 @@ const APP: () = {
         let (rx, tx, ws) = (
             // 24 & 25 are internal serial pins
             gpio.pin0.into_floating_input().into(),
+            // Needs a diode to share the line back to the host, see the README
             gpio.pin20.into_push_pull_output().into(),
             // gpio.pin25.into_floating_input().into(),
             // gpio.pin24.into_push_pull_output().into(),
@@ -51,49 +88,77 @@ const APP: () = {
 
         let ws = ws2812::Ws2812::new(timer, ws);
 
-        let mut timer = CountDownTimer::new(p.TIMER1, TimerFrequency::Freq31250Hz);
-        // 20 Hertz
-        timer.start(Hfticks::from_ms(50));
-        // Get the rx serial instance
-        let serial = Serial::uart0(p.UART0, tx, rx, hal::serial::BAUD9600)
-            .split()
-            .1;
+        let clock = Clock::new(p.TIMER1);
+        // Interrupt on every received byte, the hal doesn't do that
+        p.UART0.intenset.write(|w| w.rxdrdy().set_bit());
+        let (tx, rx) = Serial::uart0(p.UART0, tx, rx, hal::serial::BAUD9600).split();
+        let (received, to_controller) = RECEIVED.split();
 
-        init::LateResources { timer, serial, ws }
+        init::LateResources {
+            clock,
+            rx,
+            tx,
+            received,
+            to_controller,
+            ws,
+        }
+    }
+
+    #[task(binds = UART0, resources = [rx, received])]
+    fn receive(c: receive::Context) {
+        if let Ok(byte) = c.resources.rx.read() {
+            // Dropped if idle is that far behind, like a corrupted frame
+            c.resources.received.enqueue(byte).ok();
+        }
     }
 
-    #[idle(resources = [serial, timer, ws])]
+    #[idle(resources = [tx, to_controller, clock, ws])]
     fn idle(c: idle::Context) -> ! {
         // Matching resources in c3_display
-        let mut elements = Elements::new(400, 15);
-        // Chosen by fair dice roll
-        let mut rand = oorandom::Rand32::new(0);
-        // On average add a new color every 15 steps
-        let mut steps = rand.rand_range(10..20);
-        // Do something when host isn't active yet
-        // Drops first byte
-        while c.resources.serial.read().is_err() {
-            steps -= 1;
-            if steps == 0 {
-                steps = rand.rand_range(10..20);
-                elements
-                    .add_predefined(rand.rand_range(0..c3_led_tail::COLORS.len() as u32) as usize)
-                    .unwrap();
-            }
-            block!(c.resources.timer.wait()).unwrap();
-        }
-        // Host driven mode
+        // At most one color every 500 ms, so this is plenty
+        let mut elements: Elements<U64> = Elements::new(
+            Strip {
+                leds_per_metre: 60,
+                length_mm: 6_667,
+            },
+            250,
+        );
+        // Differs between devices, so the strips don't run in lockstep
+        let seed = seed::configured().unwrap_or_else(|| seed::from_id(&unique_id()));
+        let mut effects: Effects<U400> = Effects::new(seed);
+        let mut frame = [RGB8::default(); 400];
+        // Do something until the host is active or when it's gone quiet
+        let autopilot = Autopilot::new(seed);
+        // Messages for other devices are skipped
+        let mut identity = Identity::configured()
+            .unwrap_or_else(|| Identity::from_unique_id(&unique_id()).with_configured_groups());
+        identity.join("tails").ok();
+        let info = Info {
+            board: Board::Microbit,
+            firmware: version(env!("CARGO_PKG_VERSION")),
+            leds: frame.len() as u16,
+            effects: EFFECTS,
+            max_step_rate: 20,
+        };
+        let mut controller = Controller::new(elements, autopilot, identity, info);
         loop {
-            if let Ok(byte) = c.resources.serial.read().map(|x| x as usize) {
-                if byte < c3_led_tail::COLORS.len() {
-                    elements.add_predefined(byte).unwrap();
-                }
+            let now = c.resources.clock.now();
+            while let Some(byte) = c.resources.to_controller.dequeue() {
+                controller.receive(byte, now);
+            }
+            if let Some(reply) = controller.reply(now) {
+                reply.send(c.resources.tx).ok();
             }
-            if c.resources.timer.wait().is_ok() {
-                elements.step();
+            if controller.frame(now) {
+                let (effect, time) = (controller.effect(), controller.time());
+                effects.render(effect, controller.elements_mut(), time, &mut frame);
+                let brightness = controller.brightness();
+                let colors = || smart_leds::gamma(frame.iter().cloned());
+                // Both dim linearly, so the lower one stays within the budget
+                let scale = POWER_LIMIT.scale(colors()).min(brightness);
                 c.resources
                     .ws
-                    .write(smart_leds::gamma(elements.iter()))
+                    .write(Dim::new(colors(), scale))
                     .expect("Write");
             }
         }
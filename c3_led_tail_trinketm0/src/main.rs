@@ -8,17 +8,78 @@ use apa102_spi as apa102;
 use trinket_m0 as hal;
 
 use hal::clock::GenericClockController;
-use hal::delay::Delay;
 use hal::gpio::*;
 use hal::prelude::*;
 use hal::sercom::*;
 use hal::time::Hertz;
 use hal::timer::TimerCounter;
+use heapless::spsc::{Consumer, Producer, Queue};
 use instant_timer::InstantTimer;
-use nb::block;
-use smart_leds::SmartLedsWrite;
+use rtfm::Mutex;
+use smart_leds::{SmartLedsWrite, RGB8};
+
+use c3_led_tail::autopilot::Autopilot;
+use c3_led_tail::clock::Clock;
+use c3_led_tail::consts::*;
+use c3_led_tail::controller::{Controller, Info};
+use c3_led_tail::effects::*;
+use c3_led_tail::power::{ChipModel, Dim, PowerLimit};
+use c3_led_tail::seed;
+use c3_led_tail::segment::Segment;
+use c3_led_tail::{Elements, Strip};
+use c3_protocol::{version, Board, Identity};
+
+// Frequency of the core, which the clock counts
+const CORE_HZ: u32 = 48_000_000;
+// What the supply of the external apa102 strip, powered over usb can deliver
+const POWER_LIMIT: PowerLimit = PowerLimit {
+    model: ChipModel::APA102,
+    budget_ma: 450,
+};
+// Both show the start of the line
+const DOTSTAR: Segment = Segment {
+    start: 0,
+    length: 1,
+    reversed: false,
+};
+const EXTERNAL: Segment = Segment {
+    start: 0,
+    length: 80,
+    reversed: false,
+};
 
-use c3_led_tail::Elements;
+// Addresses of the words of the samd21 serial number
+const UNIQUE_ID: [usize; 4] = [0x0080_A00C, 0x0080_A040, 0x0080_A044, 0x0080_A048];
+
+fn unique_id() -> [u8; 16] {
+    let mut id = [0; 16];
+    for (i, address) in UNIQUE_ID.iter().enumerate() {
+        // NOTE(unsafe): Read only register
+        let word = unsafe { core::ptr::read_volatile(*address as *const u32) };
+        id[i * 4..i * 4 + 4].copy_from_slice(&word.to_le_bytes());
+    }
+    id
+}
+
+/// A serial shared with a task, only locked for single bytes, so the task
+/// isn't held up for a whole message
+struct Locked<'a, M>(&'a mut M);
+
+impl<M, S> embedded_hal::serial::Write<u8> for Locked<'_, M>
+where
+    M: Mutex<T = S>,
+    S: embedded_hal::serial::Write<u8>,
+{
+    type Error = S::Error;
+
+    fn write(&mut self, byte: u8) -> nb::Result<(), S::Error> {
+        self.0.lock(|serial| serial.write(byte))
+    }
+
+    fn flush(&mut self) -> nb::Result<(), S::Error> {
+        self.0.lock(|serial| serial.flush())
+    }
+}
 
 #[rtfm::app(device = trinket_m0::pac, peripherals = true)]
 const APP: () = {
@@ -40,13 +101,17 @@ const APP: () = {
                 InstantTimer,
             >,
         >,
-        delay: hal::delay::Delay,
-        timer: TimerCounter<hal::pac::TC4>,
+        clock: Clock,
         serial: UART0<Sercom0Pad3<Pa7<PfD>>, Sercom0Pad2<Pa6<PfD>>, (), ()>,
+        // Bytes from the host, received while a frame is written
+        received: Producer<'static, u8, U64>,
+        to_controller: Consumer<'static, u8, U64>,
     }
 
     #[init]
     fn init(context: init::Context) -> init::LateResources {
+        static mut RECEIVED: Queue<u8, U64> = Queue(heapless::i::Queue::new());
+
         let mut p = context.device;
 
         let mut clocks = GenericClockController::with_internal_32kosc(
@@ -57,7 +122,7 @@ const APP: () = {
         );
 
         let mut pins = crate::hal::Pins::new(p.PORT);
-        let delay = Delay::new(context.core.SYST, &mut clocks);
+        let clock = Clock::new(context.core.SYST, CORE_HZ);
 
         let (odi, oci, nc, edi, eci, enc, rx, tx) = (
             // Onboard apa102
@@ -69,6 +134,8 @@ const APP: () = {
             pins.d1.into_push_pull_output(&mut pins.port),
             pins.d2.into_floating_input(&mut pins.port),
             pins.d3.into_floating_input(&mut pins.port),
+            // Serial tx, push-pull once it's the sercom's, so it needs a diode to
+            // share the line back to the host, see the README
             pins.d4.into_floating_input(&mut pins.port),
         );
 
@@ -81,18 +148,14 @@ const APP: () = {
 
         let dotstar = apa102_spi::Apa102::new(spi);
 
-        let timer_clock = clocks.tc4_tc5(&gclk0).unwrap();
         // TODO Timer doesn't seem to work at higher frequencies
+        // let timer_clock = clocks.tc4_tc5(&gclk0).unwrap();
         // let mut timer_external = TimerCounter::tc5_(&timer_clock, p.TC5, &mut p.PM);
         // timer_external.start(5.khz());
         let timer_external = InstantTimer {};
         let spi = bitbang_hal::spi::SPI::new(apa102_spi::MODE, enc, edi, eci, timer_external);
         let external = apa102_spi::Apa102::new(spi);
-        let mut timer = TimerCounter::tc4_(&timer_clock, p.TC4, &mut p.PM);
-
-        // Has half as much leds per m as the other ones, so half the frueqency
-        timer.start(Hertz(10));
-        let serial = hal::uart(
+        let mut serial = hal::uart(
             &mut clocks,
             Hertz(9600),
             p.SERCOM0,
@@ -101,55 +164,83 @@ const APP: () = {
             tx,
             &mut pins.port,
         );
+        serial.intenset(|w| {
+            w.rxc().set_bit();
+        });
+        let (received, to_controller) = RECEIVED.split();
 
         init::LateResources {
-            delay,
+            clock,
             dotstar,
-            timer,
             serial,
+            received,
+            to_controller,
             external,
         }
     }
 
-    #[idle(resources = [delay, dotstar, serial, timer, external])]
-    fn idle(c: idle::Context) -> ! {
-        // Matching resources in c3_display
-        // Half the tail length, since half the leds per m
-        let mut elements = Elements::new(80, 8);
-        // Chosen by fair dice roll
-        let mut rand = oorandom::Rand32::new(0);
-        // On average add a new color every 15 steps
-        let mut steps = rand.rand_range(10..20);
-        // Do something when host isn't active yet
-        // Drops first byte
-        while c.resources.serial.read().is_err() {
-            steps -= 1;
-            if steps == 0 {
-                steps = rand.rand_range(10..20);
-                elements
-                    .add_predefined(rand.rand_range(0..c3_led_tail::COLORS.len() as u32) as usize)
-                    .unwrap();
-            }
-            block!(c.resources.timer.wait()).unwrap();
+    #[task(binds = SERCOM0, resources = [serial, received])]
+    fn receive(c: receive::Context) {
+        if let Ok(byte) = c.resources.serial.read() {
+            // Dropped if idle is that far behind, like a corrupted frame
+            c.resources.received.enqueue(byte).ok();
         }
-        // Host driven mode
+    }
+
+    #[idle(resources = [clock, dotstar, serial, to_controller, external])]
+    fn idle(mut c: idle::Context) -> ! {
+        // Matching resources in c3_display
+        // A comet takes about 9 s across the strip, so this holds one every
+        // 300 ms, comets added quicker push out the oldest ones
+        let mut elements: Elements<U32> = Elements::new(
+            Strip {
+                leds_per_metre: 30,
+                length_mm: 2_667,
+            },
+            250,
+        );
+        // Differs between devices, so the strips don't run in lockstep
+        let seed = seed::configured().unwrap_or_else(|| seed::from_id(&unique_id()));
+        let mut effects: Effects<U80> = Effects::new(seed);
+        // The strip is short, so the rainbow is squeezed onto it
+        effects.rainbow.spread = 20;
+        let mut frame = [RGB8::default(); 80];
+        // Do something until the host is active or when it's gone quiet
+        let autopilot = Autopilot::new(seed);
+        // Messages for other devices are skipped
+        let mut identity = Identity::configured()
+            .unwrap_or_else(|| Identity::from_unique_id(&unique_id()).with_configured_groups());
+        identity.join("tails").ok();
+        let info = Info {
+            board: Board::TrinketM0,
+            firmware: version(env!("CARGO_PKG_VERSION")),
+            leds: frame.len() as u16,
+            effects: EFFECTS,
+            max_step_rate: 50,
+        };
+        let mut controller = Controller::new(elements, autopilot, identity, info);
         loop {
-            if let Ok(byte) = c.resources.serial.read().map(|x| x as usize) {
-                if byte < c3_led_tail::COLORS.len() {
-                    elements.add_predefined(byte).unwrap();
-                }
+            let now = c.resources.clock.now();
+            while let Some(byte) = c.resources.to_controller.dequeue() {
+                controller.receive(byte, now);
+            }
+            if let Some(reply) = controller.reply(now) {
+                reply.send(&mut Locked(&mut c.resources.serial)).ok();
             }
-            if c.resources.timer.wait().is_ok() {
-                elements.step();
+            if controller.frame(now) {
+                let (effect, time) = (controller.effect(), controller.time());
+                effects.render(effect, controller.elements_mut(), time, &mut frame);
+                let brightness = controller.brightness();
                 c.resources
                     .dotstar
-                    // Only the onboard led
-                    .write(smart_leds::gamma(elements.iter()).take(1))
+                    .write(Dim::new(smart_leds::gamma(DOTSTAR.map(&frame)), brightness))
                     .expect("Write");
+                let colors = || smart_leds::gamma(EXTERNAL.map(&frame));
+                // Both dim linearly, so the lower one stays within the budget
+                let scale = POWER_LIMIT.scale(colors()).min(brightness);
                 c.resources
                     .external
-                    // Only the onboard led
-                    .write(smart_leds::gamma(elements.iter()))
+                    .write(Dim::new(colors(), scale))
                     .expect("Write");
             }
         }
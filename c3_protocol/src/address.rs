@@ -0,0 +1,114 @@
+//! Who a message is meant for
+//!
+//! Devices and groups are referred to by names, which are sent as a 16 bit
+//! hash. Devices get their name from `C3_DEVICE_ID` at build time or, without
+//! it, from their unique id, and can be in up to [`MAX_GROUPS`] groups.
+
+use crate::Error;
+
+/// Groups a single device can be in
+pub const MAX_GROUPS: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Address {
+    /// Every device
+    Broadcast,
+    /// The device with this id
+    Device(u16),
+    /// Every device in the group with this id
+    Group(u16),
+}
+
+impl Address {
+    /// The device called `name`
+    pub fn device(name: &str) -> Self {
+        Address::Device(id(name.as_bytes()))
+    }
+
+    /// Every device in the group called `name`
+    pub fn group(name: &str) -> Self {
+        Address::Group(id(name.as_bytes()))
+    }
+}
+
+/// Id of the device or group called `name`
+pub fn id(name: &[u8]) -> u16 {
+    // FNV-1a, folded to 16 bits
+    let hash = name.iter().fold(0x811c_9dc5, |hash: u32, byte| {
+        (hash ^ *byte as u32).wrapping_mul(0x0100_0193)
+    });
+    (hash >> 16) as u16 ^ hash as u16
+}
+
+/// The id of a device and the groups it's in, to filter out the messages for
+/// other devices
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Identity {
+    pub id: u16,
+    groups: [Option<u16>; MAX_GROUPS],
+}
+
+impl Identity {
+    /// A device called `name`, not in any group yet
+    pub fn new(name: &str) -> Self {
+        Self {
+            id: id(name.as_bytes()),
+            groups: [None; MAX_GROUPS],
+        }
+    }
+
+    /// A device named after its unique id, written as lowercase hex
+    pub fn from_unique_id(unique_id: &[u8]) -> Self {
+        let mut name = [0; 32];
+        let digits = b"0123456789abcdef";
+        for (i, byte) in unique_id.iter().take(name.len() / 2).enumerate() {
+            name[i * 2] = digits[(byte >> 4) as usize];
+            name[i * 2 + 1] = digits[(byte & 0xF) as usize];
+        }
+        let len = (unique_id.len() * 2).min(name.len());
+        Self {
+            id: id(&name[..len]),
+            groups: [None; MAX_GROUPS],
+        }
+    }
+
+    /// The device called `C3_DEVICE_ID` at build time, in the groups of the
+    /// comma separated `C3_DEVICE_GROUPS`, if the name is set
+    pub fn configured() -> Option<Self> {
+        option_env!("C3_DEVICE_ID").map(|name| Self::new(name).with_configured_groups())
+    }
+
+    /// Join the groups of the comma separated `C3_DEVICE_GROUPS` from build
+    /// time, as far as there's space
+    pub fn with_configured_groups(mut self) -> Self {
+        let groups = option_env!("C3_DEVICE_GROUPS").unwrap_or("");
+        for group in groups.split(',').filter(|group| !group.is_empty()) {
+            self.join(group).ok();
+        }
+        self
+    }
+
+    /// Join the group called `name`
+    pub fn join(&mut self, name: &str) -> Result<(), Error> {
+        let group = id(name.as_bytes());
+        if self.groups.contains(&Some(group)) {
+            return Ok(());
+        }
+        let free = self
+            .groups
+            .iter_mut()
+            .find(|x| x.is_none())
+            .ok_or(Error::TooManyGroups)?;
+        *free = Some(group);
+        Ok(())
+    }
+
+    /// Whether a message to `address` is meant for this device
+    pub fn accepts(&self, address: Address) -> bool {
+        match address {
+            Address::Broadcast => true,
+            Address::Device(id) => id == self.id,
+            Address::Group(id) => self.groups.contains(&Some(id)),
+        }
+    }
+}
@@ -0,0 +1,54 @@
+//! Consistent overhead byte stuffing, so frames can be delimited by zeros
+
+use crate::Error;
+
+/// Encode `data` into `out`, without the delimiter, and return the length
+pub fn encode(data: &[u8], out: &mut [u8]) -> Result<usize, Error> {
+    // Where the length of the current block goes
+    let mut code_index = 0;
+    let mut code = 1;
+    let mut len = 1;
+    for &byte in data {
+        if byte != 0 {
+            *out.get_mut(len).ok_or(Error::BufferTooSmall)? = byte;
+            len += 1;
+            code += 1;
+        }
+        // Blocks end at zeros or after 254 bytes
+        if byte == 0 || code == 0xFF {
+            *out.get_mut(code_index).ok_or(Error::BufferTooSmall)? = code;
+            code_index = len;
+            len += 1;
+            code = 1;
+        }
+    }
+    *out.get_mut(code_index).ok_or(Error::BufferTooSmall)? = code;
+    Ok(len)
+}
+
+/// Decode `data`, without the delimiter, into `out` and return the length
+pub fn decode(data: &[u8], out: &mut [u8]) -> Result<usize, Error> {
+    let mut i = 0;
+    let mut len = 0;
+    while i < data.len() {
+        let code = data[i] as usize;
+        let end = i + code;
+        if code == 0 || end > data.len() {
+            return Err(Error::Cobs);
+        }
+        for &byte in &data[i + 1..end] {
+            if byte == 0 {
+                return Err(Error::Cobs);
+            }
+            *out.get_mut(len).ok_or(Error::BufferTooSmall)? = byte;
+            len += 1;
+        }
+        i = end;
+        // Full blocks and the last one don't end with a zero
+        if code != 0xFF && i < data.len() {
+            *out.get_mut(len).ok_or(Error::BufferTooSmall)? = 0;
+            len += 1;
+        }
+    }
+    Ok(len)
+}
@@ -0,0 +1,358 @@
+//! The serial protocol between the host and the devices
+//!
+//! Every message is sent as one frame: the protocol [`VERSION`], the
+//! [`Address`] of the receivers, the tag of the message, its payload and a
+//! CRC-16 over all of that. The frame is COBS
+//! encoded, so it doesn't contain any zeros, and terminated by a zero.
+//! Receivers feed the bytes into a [`Decoder`], which drops frames that are
+//! corrupted, from another version of the protocol or meant for other devices.
+#![no_std]
+
+mod address;
+mod cobs;
+mod crc;
+mod status;
+
+pub use address::{id, Address, Identity, MAX_GROUPS};
+pub use crc::crc16;
+pub use status::{
+    answer_delay, heartbeat, version, Board, Commands, Status, UnknownBoard, HEARTBEAT,
+    STATUS_SLOT, STATUS_SLOTS,
+};
+
+/// Increase when the frame layout or any message changes
+pub const VERSION: u8 = 4;
+/// Length of the longest encoded frame, including the delimiter
+pub const MAX_FRAME: usize = 64;
+
+// Version, address, tag and checksum
+const OVERHEAD: usize = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The frame doesn't fit into the buffer
+    BufferTooSmall,
+    /// The frame isn't valid COBS
+    Cobs,
+    /// The frame is too short for a version, a tag and a checksum
+    TooShort,
+    /// The checksum doesn't match
+    Crc,
+    /// The frame is from another version of the protocol
+    Version(u8),
+    /// No message has this tag
+    UnknownMessage(u8),
+    /// The payload doesn't have the length of the message
+    Length,
+    /// No address has this kind
+    UnknownAddress(u8),
+    /// A device can't join any more groups
+    TooManyGroups,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message {
+    /// The host takes over, the tails answer with `Ack`
+    Hello,
+    /// Answer to `Hello`
+    Ack,
+    /// Ask the devices for their `Status`
+    Identify,
+    /// Answer to `Identify`, also sent unasked every `heartbeat`
+    Status(Status),
+    /// Show image `n` on the display and a comet with color `n` of the
+    /// palette on the tails
+    Show(u8),
+    /// Switch the tails to one of their built-in effects
+    Effect(u8),
+    /// Replace the color at `index` of the palette of the tails, or add it if
+    /// it's just past the end
+    SetColor { index: u8, color: [u8; 3] },
+    /// Add a comet with any color to the tails, on rgbw strips the white die
+    /// takes over what it can of it, like for all other colors
+    Comet {
+        color: [u8; 3],
+        /// Drives the white die of rgbw strips on top, is mixed into the
+        /// color on others
+        white: Option<u8>,
+        /// Zero for the trail length of the tail
+        trail_length_mm: u16,
+    },
+    /// Set the brightness of the tails, 255 is full brightness
+    Brightness(u8),
+    /// Set how often the tails move the comets and draw a frame, in Hz
+    StepRate(u8),
+    /// Set the trail length of new comets on the tails, in mm
+    TrailLength(u16),
+    /// Clear the display
+    Clear,
+    /// Fade ferris in and out on the display
+    Fade,
+}
+
+// Kinds of addresses
+const BROADCAST: u8 = 0x00;
+const DEVICE: u8 = 0x01;
+const GROUP: u8 = 0x02;
+
+// Tags of the messages
+const HELLO: u8 = 0x01;
+const ACK: u8 = 0x02;
+const SHOW: u8 = 0x10;
+const EFFECT: u8 = 0x11;
+const SET_COLOR: u8 = 0x12;
+const COMET: u8 = 0x13;
+const BRIGHTNESS: u8 = 0x14;
+const STEP_RATE: u8 = 0x15;
+const TRAIL_LENGTH: u8 = 0x16;
+const CLEAR: u8 = 0x20;
+const FADE: u8 = 0x21;
+const IDENTIFY: u8 = 0x03;
+const STATUS: u8 = 0x04;
+// Tags with the names of their messages, the order gives the bits of
+// `Commands`
+const KINDS: [(u8, &str); 13] = [
+    (HELLO, "hello"),
+    (ACK, "ack"),
+    (IDENTIFY, "identify"),
+    (STATUS, "status"),
+    (SHOW, "show"),
+    (EFFECT, "effect"),
+    (SET_COLOR, "set color"),
+    (COMET, "comet"),
+    (BRIGHTNESS, "brightness"),
+    (STEP_RATE, "step rate"),
+    (TRAIL_LENGTH, "trail length"),
+    (CLEAR, "clear"),
+    (FADE, "fade"),
+];
+
+impl Message {
+    fn tag(&self) -> u8 {
+        match self {
+            Message::Hello => HELLO,
+            Message::Ack => ACK,
+            Message::Identify => IDENTIFY,
+            Message::Status(_) => STATUS,
+            Message::Show(_) => SHOW,
+            Message::Effect(_) => EFFECT,
+            Message::SetColor { .. } => SET_COLOR,
+            Message::Comet { .. } => COMET,
+            Message::Brightness(_) => BRIGHTNESS,
+            Message::StepRate(_) => STEP_RATE,
+            Message::TrailLength(_) => TRAIL_LENGTH,
+            Message::Clear => CLEAR,
+            Message::Fade => FADE,
+        }
+    }
+
+    /// Write the payload into `buf` and return its length
+    fn payload(&self, buf: &mut [u8]) -> usize {
+        match *self {
+            Message::Hello | Message::Ack | Message::Identify | Message::Clear | Message::Fade => 0,
+            Message::Status(status) => status.write(buf),
+            Message::Show(n)
+            | Message::Effect(n)
+            | Message::Brightness(n)
+            | Message::StepRate(n) => {
+                buf[0] = n;
+                1
+            }
+            Message::SetColor { index, color } => {
+                buf[0] = index;
+                buf[1..4].copy_from_slice(&color);
+                4
+            }
+            Message::Comet {
+                color,
+                white,
+                trail_length_mm,
+            } => {
+                buf[..3].copy_from_slice(&color);
+                // The white die is left out if there's none
+                let len = match white {
+                    Some(white) => {
+                        buf[3] = white;
+                        4
+                    }
+                    None => 3,
+                };
+                buf[len..len + 2].copy_from_slice(&trail_length_mm.to_be_bytes());
+                len + 2
+            }
+            Message::TrailLength(mm) => {
+                buf[..2].copy_from_slice(&mm.to_be_bytes());
+                2
+            }
+        }
+    }
+
+    fn parse(tag: u8, payload: &[u8]) -> Result<Self, Error> {
+        match (tag, payload) {
+            (HELLO, []) => Ok(Message::Hello),
+            (ACK, []) => Ok(Message::Ack),
+            (IDENTIFY, []) => Ok(Message::Identify),
+            (STATUS, payload) => Status::parse(payload).map(Message::Status),
+            (SHOW, [n]) => Ok(Message::Show(*n)),
+            (EFFECT, [n]) => Ok(Message::Effect(*n)),
+            (SET_COLOR, [index, r, g, b]) => Ok(Message::SetColor {
+                index: *index,
+                color: [*r, *g, *b],
+            }),
+            (COMET, [r, g, b, t0, t1]) => Ok(Message::Comet {
+                color: [*r, *g, *b],
+                white: None,
+                trail_length_mm: u16::from_be_bytes([*t0, *t1]),
+            }),
+            (COMET, [r, g, b, w, t0, t1]) => Ok(Message::Comet {
+                color: [*r, *g, *b],
+                white: Some(*w),
+                trail_length_mm: u16::from_be_bytes([*t0, *t1]),
+            }),
+            (BRIGHTNESS, [n]) => Ok(Message::Brightness(*n)),
+            (STEP_RATE, [n]) => Ok(Message::StepRate(*n)),
+            (TRAIL_LENGTH, [a, b]) => Ok(Message::TrailLength(u16::from_be_bytes([*a, *b]))),
+            (CLEAR, []) => Ok(Message::Clear),
+            (FADE, []) => Ok(Message::Fade),
+            (tag, _) if KINDS.iter().any(|(x, _)| *x == tag) => Err(Error::Length),
+            (tag, _) => Err(Error::UnknownMessage(tag)),
+        }
+    }
+
+    /// Write the frame of this message to every device, including the
+    /// delimiter, into `buf` and return its length
+    pub fn encode(&self, buf: &mut [u8]) -> Result<usize, Error> {
+        self.encode_to(Address::Broadcast, buf)
+    }
+
+    /// Encode the message like `encode` and write it to `serial`, e.g. to
+    /// answer the host
+    #[cfg(feature = "serial")]
+    pub fn send<S>(&self, serial: &mut S) -> Result<(), S::Error>
+    where
+        S: embedded_hal::serial::Write<u8>,
+    {
+        let mut buf = [0; MAX_FRAME];
+        // Every message fits into a frame
+        let len = self.encode(&mut buf).unwrap_or(0);
+        for byte in &buf[..len] {
+            nb::block!(serial.write(*byte))?;
+        }
+        Ok(())
+    }
+
+    /// Like `encode`, but only for the devices at `address`
+    pub fn encode_to(&self, address: Address, buf: &mut [u8]) -> Result<usize, Error> {
+        let mut raw = [0; MAX_FRAME];
+        let (kind, id) = match address {
+            Address::Broadcast => (BROADCAST, 0),
+            Address::Device(id) => (DEVICE, id),
+            Address::Group(id) => (GROUP, id),
+        };
+        raw[0] = VERSION;
+        raw[1] = kind;
+        raw[2..4].copy_from_slice(&id.to_be_bytes());
+        raw[4] = self.tag();
+        let len = 5 + self.payload(&mut raw[5..]);
+        let crc = crc16(&raw[..len]);
+        raw[len..len + 2].copy_from_slice(&crc.to_be_bytes());
+        let len = cobs::encode(&raw[..len + 2], buf)?;
+        *buf.get_mut(len).ok_or(Error::BufferTooSmall)? = 0;
+        Ok(len + 1)
+    }
+
+    /// Read a message and who it's meant for from a `frame` without the
+    /// delimiter
+    pub fn decode(frame: &[u8]) -> Result<(Address, Self), Error> {
+        let mut raw = [0; MAX_FRAME];
+        let len = cobs::decode(frame, &mut raw)?;
+        if len < OVERHEAD {
+            return Err(Error::TooShort);
+        }
+        let (data, crc) = raw[..len].split_at(len - 2);
+        if crc16(data) != u16::from_be_bytes([crc[0], crc[1]]) {
+            return Err(Error::Crc);
+        }
+        if data[0] != VERSION {
+            return Err(Error::Version(data[0]));
+        }
+        let id = u16::from_be_bytes([data[2], data[3]]);
+        let address = match data[1] {
+            BROADCAST => Address::Broadcast,
+            DEVICE => Address::Device(id),
+            GROUP => Address::Group(id),
+            kind => return Err(Error::UnknownAddress(kind)),
+        };
+        Ok((address, Self::parse(data[4], &data[5..])?))
+    }
+}
+
+/// Splits the received bytes into frames and decodes them
+pub struct Decoder {
+    buf: [u8; MAX_FRAME],
+    len: usize,
+    // The current frame didn't fit into `buf`
+    overflow: bool,
+    // Only messages for this device are returned, if set
+    identity: Option<Identity>,
+}
+
+impl Decoder {
+    /// Decoder for all messages, regardless of who they're meant for
+    pub fn new() -> Self {
+        Self {
+            buf: [0; MAX_FRAME],
+            len: 0,
+            overflow: false,
+            identity: None,
+        }
+    }
+
+    /// Decoder that skips the messages for other devices
+    pub fn for_device(identity: Identity) -> Self {
+        Self {
+            identity: Some(identity),
+            ..Self::new()
+        }
+    }
+
+    /// Add a received byte, returning the message once a frame is complete
+    ///
+    /// A frame that was cut off, e.g. because the device started listening in
+    /// the middle of it, results in an error and the next one is read as
+    /// usual.
+    pub fn feed(&mut self, byte: u8) -> Option<Result<Message, Error>> {
+        if byte != 0 {
+            if self.len < self.buf.len() {
+                self.buf[self.len] = byte;
+                self.len += 1;
+            } else {
+                self.overflow = true;
+            }
+            return None;
+        }
+        let (len, overflow) = (self.len, self.overflow);
+        self.len = 0;
+        self.overflow = false;
+        if overflow {
+            Some(Err(Error::BufferTooSmall))
+        } else if len == 0 {
+            // Nothing between two delimiters
+            None
+        } else {
+            match Message::decode(&self.buf[..len]) {
+                Ok((address, message)) => match self.identity {
+                    Some(identity) if !identity.accepts(address) => None,
+                    _ => Some(Ok(message)),
+                },
+                Err(e) => Some(Err(e)),
+            }
+        }
+    }
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,187 @@
+//! What devices report about themselves when asked with `Identify`
+
+use crate::{Error, Message, KINDS};
+
+/// Devices answer `Identify` and `Hello` in one of this many slots, see
+/// [`answer_delay`]
+pub const STATUS_SLOTS: u16 = 8;
+/// Long enough to send a `Status` at 9600 baud, even if a device is still
+/// busy writing a frame when its slot starts
+pub const STATUS_SLOT: u16 = 60;
+/// Devices send their `Status` unasked about this often in ms, see
+/// [`heartbeat`]
+pub const HEARTBEAT: u16 = 5_000;
+
+// Most the heartbeat of a device is longer than `HEARTBEAT` in ms
+const HEARTBEAT_SPREAD: u16 = 1_000;
+
+/// Time in ms device `id` waits before its answer to the `round`th
+/// `Identify` or `Hello` it got
+///
+/// The slot changes every round, so two devices whose answers collided are
+/// unlikely to collide again when asked once more.
+pub fn answer_delay(id: u16, round: u16) -> u16 {
+    // Mixes all bits of both into the low ones
+    let mut x = (id as u32) << 16 | round as u32;
+    x = (x ^ x >> 16).wrapping_mul(0x045d_9f3b);
+    x = (x ^ x >> 16).wrapping_mul(0x045d_9f3b);
+    x ^= x >> 16;
+    (x % STATUS_SLOTS as u32) as u16 * STATUS_SLOT
+}
+
+/// Time in ms between two heartbeats of device `id`
+///
+/// Differs between devices, so those started at the same time don't keep
+/// sending their heartbeats at once.
+pub fn heartbeat(id: u16) -> u32 {
+    (HEARTBEAT + id % HEARTBEAT_SPREAD) as u32
+}
+
+// Length of the payload of `Message::Status`
+const LEN: usize = 17;
+
+/// Which firmware a device runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Board {
+    G071,
+    Microbit,
+    TrinketM0,
+    Display,
+    /// From a newer version of the protocol
+    Unknown(UnknownBoard),
+}
+
+/// Number of a board this version of the protocol doesn't know
+///
+/// Only comes from `Board::from`, so it never has the number of a known
+/// board.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownBoard(u8);
+
+impl UnknownBoard {
+    pub fn number(self) -> u8 {
+        self.0
+    }
+}
+
+impl From<u8> for Board {
+    fn from(n: u8) -> Self {
+        match n {
+            0 => Board::G071,
+            1 => Board::Microbit,
+            2 => Board::TrinketM0,
+            3 => Board::Display,
+            n => Board::Unknown(UnknownBoard(n)),
+        }
+    }
+}
+
+impl From<Board> for u8 {
+    fn from(board: Board) -> u8 {
+        match board {
+            Board::G071 => 0,
+            Board::Microbit => 1,
+            Board::TrinketM0 => 2,
+            Board::Display => 3,
+            Board::Unknown(n) => n.0,
+        }
+    }
+}
+
+/// The messages a device understands
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Commands(pub u32);
+
+impl Commands {
+    /// The kinds of `messages`, regardless of their contents
+    pub fn of(messages: &[Message]) -> Self {
+        let bits = messages.iter().fold(0, |bits, x| bits | bit(x));
+        Commands(bits)
+    }
+
+    pub fn supports(self, message: &Message) -> bool {
+        self.0 & bit(message) != 0
+    }
+
+    /// Names of the supported messages, e.g. to show them to a user
+    pub fn names(self) -> impl Iterator<Item = &'static str> {
+        KINDS
+            .iter()
+            .enumerate()
+            .filter(move |(i, _)| self.0 & 1 << i != 0)
+            .map(|(_, (_, name))| *name)
+    }
+}
+
+fn bit(message: &Message) -> u32 {
+    let tag = message.tag();
+    KINDS
+        .iter()
+        .position(|(x, _)| *x == tag)
+        .map(|i| 1 << i)
+        .unwrap_or(0)
+}
+
+/// Answer of a device to `Identify`, and its heartbeat
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Status {
+    /// Id of the device, see `Identity`
+    pub id: u16,
+    pub board: Board,
+    /// Major, minor and patch version of the firmware
+    pub firmware: [u8; 3],
+    pub leds: u16,
+    pub commands: Commands,
+    /// Frames per second, zero if the device doesn't draw any on its own
+    pub step_rate: u8,
+    /// Frames dropped since the start because they were corrupted
+    pub corrupted: u16,
+    /// Messages since the start that couldn't be carried out, e.g. because
+    /// a color isn't in the palette
+    pub failed: u16,
+}
+
+impl Status {
+    /// Write the payload into `buf` and return its length
+    pub(crate) fn write(&self, buf: &mut [u8]) -> usize {
+        buf[0..2].copy_from_slice(&self.id.to_be_bytes());
+        buf[2] = self.board.into();
+        buf[3..6].copy_from_slice(&self.firmware);
+        buf[6..8].copy_from_slice(&self.leds.to_be_bytes());
+        buf[8..12].copy_from_slice(&self.commands.0.to_be_bytes());
+        buf[12] = self.step_rate;
+        buf[13..15].copy_from_slice(&self.corrupted.to_be_bytes());
+        buf[15..17].copy_from_slice(&self.failed.to_be_bytes());
+        LEN
+    }
+
+    pub(crate) fn parse(payload: &[u8]) -> Result<Self, Error> {
+        if payload.len() != LEN {
+            return Err(Error::Length);
+        }
+        let u16_at = |i: usize| u16::from_be_bytes([payload[i], payload[i + 1]]);
+        Ok(Status {
+            id: u16_at(0),
+            board: payload[2].into(),
+            firmware: [payload[3], payload[4], payload[5]],
+            leds: u16_at(6),
+            commands: Commands(u32::from_be_bytes([
+                payload[8],
+                payload[9],
+                payload[10],
+                payload[11],
+            ])),
+            step_rate: payload[12],
+            corrupted: u16_at(13),
+            failed: u16_at(15),
+        })
+    }
+}
+
+/// Firmware version from a `major.minor.patch` string like
+/// `CARGO_PKG_VERSION`, parts that aren't numbers are zero
+pub fn version(version: &str) -> [u8; 3] {
+    let mut parts = version.split('.').map(|x| x.parse().unwrap_or(0));
+    let mut part = || parts.next().unwrap_or(0);
+    [part(), part(), part()]
+}
@@ -0,0 +1,86 @@
+use c3_protocol::{id, Address, Decoder, Error, Identity, Message, MAX_FRAME, MAX_GROUPS};
+
+fn encode_to(address: Address, message: Message) -> Vec<u8> {
+    let mut buf = [0; MAX_FRAME];
+    let len = message.encode_to(address, &mut buf).unwrap();
+    buf[..len].to_vec()
+}
+
+fn feed(decoder: &mut Decoder, bytes: &[u8]) -> Vec<Result<Message, Error>> {
+    bytes
+        .iter()
+        .filter_map(|byte| decoder.feed(*byte))
+        .collect()
+}
+
+#[test]
+fn roundtrip() {
+    for address in [
+        Address::Broadcast,
+        Address::device("left"),
+        Address::group("tails"),
+    ]
+    .iter()
+    {
+        let frame = encode_to(*address, Message::Show(1));
+        assert_eq!(
+            Message::decode(&frame[..frame.len() - 1]),
+            Ok((*address, Message::Show(1)))
+        );
+    }
+}
+
+#[test]
+fn names_differ() {
+    assert_ne!(id(b"left"), id(b"right"));
+    assert_eq!(Address::device("left"), Address::Device(id(b"left")));
+}
+
+#[test]
+fn unique_id_as_hex() {
+    let identity = Identity::from_unique_id(&[0x12, 0xab, 0x00]);
+    assert_eq!(identity.id, id(b"12ab00"));
+}
+
+#[test]
+fn filters_other_devices() {
+    let mut identity = Identity::new("left");
+    identity.join("tails").unwrap();
+    let mut decoder = Decoder::for_device(identity);
+    let stream: Vec<u8> = [
+        (Address::device("right"), Message::Show(0)),
+        (Address::device("left"), Message::Show(1)),
+        (Address::group("display"), Message::Show(2)),
+        (Address::group("tails"), Message::Show(3)),
+        (Address::Broadcast, Message::Show(4)),
+    ]
+    .iter()
+    .flat_map(|(address, message)| encode_to(*address, *message))
+    .collect();
+    assert_eq!(
+        feed(&mut decoder, &stream),
+        vec![
+            Ok(Message::Show(1)),
+            Ok(Message::Show(3)),
+            Ok(Message::Show(4))
+        ]
+    );
+}
+
+#[test]
+fn host_decoder_gets_everything() {
+    let mut decoder = Decoder::new();
+    let stream = encode_to(Address::device("right"), Message::Ack);
+    assert_eq!(feed(&mut decoder, &stream), vec![Ok(Message::Ack)]);
+}
+
+#[test]
+fn limited_groups() {
+    let mut identity = Identity::new("left");
+    for i in 0..MAX_GROUPS {
+        identity.join(&i.to_string()).unwrap();
+    }
+    // Joining twice doesn't need more space
+    assert_eq!(identity.join("0"), Ok(()));
+    assert_eq!(identity.join("full"), Err(Error::TooManyGroups));
+}
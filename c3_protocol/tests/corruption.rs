@@ -0,0 +1,113 @@
+use c3_protocol::{crc16, Decoder, Error, Message, MAX_FRAME, VERSION};
+
+fn encode(message: &Message) -> Vec<u8> {
+    let mut buf = [0; MAX_FRAME];
+    let len = message.encode(&mut buf).unwrap();
+    buf[..len].to_vec()
+}
+
+/// Encode arbitrary bytes into a frame like `Message::encode` does
+fn frame(data: &[u8]) -> Vec<u8> {
+    let mut raw = data.to_vec();
+    raw.extend(&crc16(data).to_be_bytes());
+    // Simple COBS, the data is shorter than a block
+    assert!(raw.len() < 254);
+    let mut frame = Vec::new();
+    for block in raw.split(|byte| *byte == 0) {
+        frame.push(block.len() as u8 + 1);
+        frame.extend(block);
+    }
+    frame
+}
+
+#[test]
+fn every_flipped_bit_is_detected() {
+    let frame = encode(&Message::SetColor {
+        index: 1,
+        color: [67, 82, 255],
+    });
+    let content = &frame[..frame.len() - 1];
+    for i in 0..content.len() {
+        for bit in 0..8 {
+            let mut corrupted = content.to_vec();
+            corrupted[i] ^= 1 << bit;
+            assert!(
+                Message::decode(&corrupted).is_err(),
+                "Flipping bit {} of byte {} wasn't detected",
+                bit,
+                i
+            );
+        }
+    }
+}
+
+#[test]
+fn dropped_byte_is_detected() {
+    let frame = encode(&Message::Show(3));
+    let content = &frame[..frame.len() - 1];
+    for i in 0..content.len() {
+        let mut corrupted = content.to_vec();
+        corrupted.remove(i);
+        assert!(Message::decode(&corrupted).is_err());
+    }
+}
+
+#[test]
+fn other_version() {
+    assert_eq!(
+        Message::decode(&frame(&[VERSION + 1, 0, 0, 0, 0x01])),
+        Err(Error::Version(VERSION + 1))
+    );
+}
+
+#[test]
+fn unknown_address() {
+    assert_eq!(
+        Message::decode(&frame(&[VERSION, 0x7F, 0, 0, 0x01])),
+        Err(Error::UnknownAddress(0x7F))
+    );
+}
+
+#[test]
+fn unknown_message() {
+    assert_eq!(
+        Message::decode(&frame(&[VERSION, 0, 0, 0, 0xEE])),
+        Err(Error::UnknownMessage(0xEE))
+    );
+}
+
+#[test]
+fn wrong_length() {
+    assert_eq!(
+        Message::decode(&frame(&[VERSION, 0, 0, 0, 0x10, 1, 2])),
+        Err(Error::Length)
+    );
+}
+
+#[test]
+fn too_short() {
+    assert_eq!(Message::decode(&[6, 2, 1, 1, 1, 1]), Err(Error::TooShort));
+}
+
+#[test]
+fn resyncs_after_partial_frame() {
+    let mut decoder = Decoder::new();
+    let first = encode(&Message::Show(1));
+    // Started listening in the middle of the first frame
+    let mut stream = first[3..].to_vec();
+    stream.extend(encode(&Message::Show(2)));
+    let decoded: Vec<_> = stream.iter().filter_map(|b| decoder.feed(*b)).collect();
+    assert_eq!(decoded.len(), 2);
+    assert!(decoded[0].is_err());
+    assert_eq!(decoded[1], Ok(Message::Show(2)));
+}
+
+#[test]
+fn overlong_frame() {
+    let mut decoder = Decoder::new();
+    let mut stream = vec![0xAA; MAX_FRAME * 2];
+    stream.push(0);
+    stream.extend(encode(&Message::Fade));
+    let decoded: Vec<_> = stream.iter().filter_map(|b| decoder.feed(*b)).collect();
+    assert_eq!(decoded, vec![Err(Error::BufferTooSmall), Ok(Message::Fade)]);
+}
@@ -0,0 +1,107 @@
+use c3_protocol::{crc16, Address, Board, Commands, Decoder, Error, Message, Status, MAX_FRAME};
+
+const MESSAGES: [Message; 15] = [
+    Message::Hello,
+    Message::Ack,
+    Message::Identify,
+    Message::Status(Status {
+        id: 0xBEEF,
+        board: Board::TrinketM0,
+        firmware: [0, 1, 0],
+        leds: 80,
+        commands: Commands(0x1234_5678),
+        step_rate: 20,
+        corrupted: 3,
+        failed: 0xFFFF,
+    }),
+    Message::Show(0),
+    Message::Show(4),
+    Message::Effect(0x10),
+    Message::SetColor {
+        index: 2,
+        color: [0, 187, 0],
+    },
+    Message::Comet {
+        color: [255, 0, 128],
+        white: None,
+        trail_length_mm: 0,
+    },
+    Message::Comet {
+        color: [0, 0, 0],
+        white: Some(255),
+        trail_length_mm: 1_000,
+    },
+    Message::Brightness(128),
+    Message::StepRate(20),
+    Message::TrailLength(0x1234),
+    Message::Clear,
+    Message::Fade,
+];
+
+fn encode(message: &Message) -> Vec<u8> {
+    let mut buf = [0; MAX_FRAME];
+    let len = message.encode(&mut buf).unwrap();
+    buf[..len].to_vec()
+}
+
+fn feed(decoder: &mut Decoder, bytes: &[u8]) -> Vec<Result<Message, Error>> {
+    bytes
+        .iter()
+        .filter_map(|byte| decoder.feed(*byte))
+        .collect()
+}
+
+#[test]
+fn crc_check_value() {
+    assert_eq!(crc16(b"123456789"), 0x29B1);
+}
+
+#[test]
+fn frames_end_with_the_only_zero() {
+    for message in MESSAGES.iter() {
+        let frame = encode(message);
+        assert_eq!(frame.last(), Some(&0));
+        assert!(!frame[..frame.len() - 1].contains(&0));
+    }
+}
+
+#[test]
+fn roundtrip() {
+    for message in MESSAGES.iter() {
+        let frame = encode(message);
+        assert_eq!(
+            Message::decode(&frame[..frame.len() - 1]),
+            Ok((Address::Broadcast, *message))
+        );
+    }
+}
+
+#[test]
+fn decoder_roundtrip() {
+    let mut decoder = Decoder::new();
+    let stream: Vec<u8> = MESSAGES.iter().flat_map(encode).collect();
+    let decoded = feed(&mut decoder, &stream);
+    let expected: Vec<_> = MESSAGES.iter().cloned().map(Ok).collect();
+    assert_eq!(decoded, expected);
+}
+
+#[test]
+fn ignores_empty_frames() {
+    let mut decoder = Decoder::new();
+    let mut stream = vec![0, 0];
+    stream.extend(encode(&Message::Hello));
+    assert_eq!(feed(&mut decoder, &stream), vec![Ok(Message::Hello)]);
+}
+
+#[test]
+fn too_small_buffer() {
+    let mut buf = [0; 4];
+    assert_eq!(
+        Message::SetColor {
+            index: 0,
+            color: [1, 2, 3]
+        }
+        .encode(&mut buf),
+        Err(Error::BufferTooSmall)
+    );
+}
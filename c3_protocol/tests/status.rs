@@ -0,0 +1,74 @@
+use c3_protocol::{
+    answer_delay, heartbeat, id, version, Board, Commands, Message, HEARTBEAT, STATUS_SLOT,
+    STATUS_SLOTS,
+};
+
+#[test]
+fn supported_commands() {
+    let commands = Commands::of(&[Message::Hello, Message::Show(0), Message::Fade]);
+    assert!(commands.supports(&Message::Show(3)));
+    assert!(!commands.supports(&Message::Effect(0)));
+    assert_eq!(
+        commands.names().collect::<Vec<_>>(),
+        vec!["hello", "show", "fade"]
+    );
+}
+
+#[test]
+fn boards() {
+    for board in [
+        Board::G071,
+        Board::Microbit,
+        Board::TrinketM0,
+        Board::Display,
+    ]
+    .iter()
+    {
+        assert_eq!(Board::from(u8::from(*board)), *board);
+    }
+    for n in 0..=255 {
+        let board = Board::from(n);
+        assert_eq!(u8::from(board), n);
+        if let Board::Unknown(unknown) = board {
+            assert!(n > 3);
+            assert_eq!(unknown.number(), n);
+        }
+    }
+}
+
+#[test]
+fn versions() {
+    assert_eq!(version("0.1.0"), [0, 1, 0]);
+    assert_eq!(version("1.2.3-alpha"), [1, 2, 0]);
+    assert_eq!(version("4"), [4, 0, 0]);
+}
+
+#[test]
+fn answers_in_slots() {
+    for id in 0..1_000 {
+        for round in 0..4 {
+            let delay = answer_delay(id, round);
+            assert_eq!(delay % STATUS_SLOT, 0);
+            assert!(delay < STATUS_SLOTS * STATUS_SLOT);
+        }
+    }
+}
+
+#[test]
+fn slots_change_every_round() {
+    let (a, b) = (id(b"left"), id(b"right"));
+    let collisions = (0..1_000)
+        .filter(|round| answer_delay(a, *round) == answer_delay(b, *round))
+        .count();
+    // About one in `STATUS_SLOTS`
+    assert!(collisions > 60 && collisions < 190, "{}", collisions);
+    for id in 0..100 {
+        assert!((0..8).any(|round| answer_delay(id, round) != answer_delay(id, 0)));
+    }
+}
+
+#[test]
+fn heartbeats_differ() {
+    assert!(heartbeat(id(b"left")) >= HEARTBEAT as u32);
+    assert_ne!(heartbeat(1), heartbeat(2));
+}